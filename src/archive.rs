@@ -0,0 +1,95 @@
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::{Error, Result};
+
+/// Download archive: a text file listing downloaded videos as `<platform> <id>` lines,
+/// e.g. `youtube dQw4w9WgXcQ`, so that later runs can skip them.
+///
+/// Clones share the same entries, so parallel downloads can record into one archive.
+#[derive(Debug, Clone)]
+pub struct Archive {
+    path: PathBuf,
+    entries: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Archive {
+    /// Read the archive at `path`; a missing file is an empty archive
+    pub fn load(path: &Path) -> Result<Self> {
+        let entries = match std::fs::read_to_string(path) {
+            Ok(text) => text.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(str::to_string)
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashSet::new(),
+            Err(e) => return Err(Error::IoError(format!("Failed to read download archive {:?}: {}", path, e))),
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries: Arc::new(Mutex::new(entries)),
+        })
+    }
+
+    pub fn contains(&self, platform: &str, id: &str) -> bool {
+        self.entries.lock().unwrap().contains(&key(platform, id))
+    }
+
+    /// Add a video to the archive and append it to the file
+    pub fn record(&self, platform: &str, id: &str) -> Result<()> {
+        let key = key(platform, id);
+        let mut entries = self.entries.lock().unwrap();
+        if entries.contains(&key) {
+            return Ok(());
+        }
+
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)
+            .map_err(|e| Error::IoError(format!("Failed to open download archive {:?}: {}", self.path, e)))?;
+        writeln!(file, "{}", key)?;
+
+        entries.insert(key);
+        Ok(())
+    }
+}
+
+/// Archive line for a video; platform names are lowercased so that entries don't depend
+/// on how a platform spells its name
+fn key(platform: &str, id: &str) -> String {
+    format!("{} {}", platform.to_lowercase(), id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_record_and_reload() {
+        let path = std::env::temp_dir().join(format!("video_dl_archive_{}.txt", uuid::Uuid::new_v4()));
+
+        let archive = Archive::load(&path).unwrap();
+        assert!(!archive.contains("YouTube", "dQw4w9WgXcQ"));
+
+        archive.record("YouTube", "dQw4w9WgXcQ").unwrap();
+        archive.record("youtube", "dQw4w9WgXcQ").unwrap();
+        archive.record("TikTok", "7234567890123456789").unwrap();
+        assert!(archive.contains("youtube", "dQw4w9WgXcQ"));
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "youtube dQw4w9WgXcQ\ntiktok 7234567890123456789\n"
+        );
+
+        let reloaded = Archive::load(&path).unwrap();
+        assert!(reloaded.contains("TikTok", "7234567890123456789"));
+        assert!(!reloaded.contains("reddit", "dQw4w9WgXcQ"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,132 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+
+/// What to do when an output file already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnExisting {
+    /// Keep the existing file and don't download again
+    #[default]
+    Skip,
+    /// Replace the existing file
+    Overwrite,
+    /// Write to a free name with a numeric suffix, e.g. `Title (1).mp4`
+    Rename,
+    /// Stop with an error
+    Fail,
+}
+
+impl FromStr for OnExisting {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "skip" => Ok(OnExisting::Skip),
+            "overwrite" => Ok(OnExisting::Overwrite),
+            "rename" => Ok(OnExisting::Rename),
+            "fail" => Ok(OnExisting::Fail),
+            other => Err(Error::InvalidArgument(format!(
+                "Unknown existing file strategy: {} (expected skip, overwrite, rename or fail)", other
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for OnExisting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OnExisting::Skip => write!(f, "skip"),
+            OnExisting::Overwrite => write!(f, "overwrite"),
+            OnExisting::Rename => write!(f, "rename"),
+            OnExisting::Fail => write!(f, "fail"),
+        }
+    }
+}
+
+/// How an output path was settled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// Nothing was in the way
+    New,
+    /// The file exists and was kept
+    Skipped,
+    /// The file exists and is replaced
+    Overwritten,
+    /// The file exists, so another name was picked
+    Renamed,
+}
+
+impl Decision {
+    /// Note for summaries, if anything was in the way
+    pub fn note(self) -> Option<&'static str> {
+        match self {
+            Decision::New => None,
+            Decision::Skipped => Some("skipped, file already exists"),
+            Decision::Overwritten => Some("overwrote existing file"),
+            Decision::Renamed => Some("renamed, file already exists"),
+        }
+    }
+}
+
+/// Settle where to write `path` when it may already exist.
+///
+/// `final_path` maps a candidate to the file that ends up on disk, which differs from the
+/// download when post-processing changes the extension; that is the file checked.
+/// Overwriting leaves the removal to the caller, which knows when it is safe.
+pub fn resolve(
+    path: PathBuf,
+    on_existing: OnExisting,
+    final_path: impl Fn(&Path) -> PathBuf
+) -> Result<(PathBuf, Decision)> {
+    let target = final_path(&path);
+    if !target.exists() {
+        return Ok((path, Decision::New));
+    }
+
+    match on_existing {
+        OnExisting::Skip => Ok((target, Decision::Skipped)),
+        OnExisting::Overwrite => Ok((path, Decision::Overwritten)),
+        OnExisting::Fail => Err(Error::FileExists(target)),
+        OnExisting::Rename => {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+            let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+            let renamed = (1..)
+                .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, extension)))
+                .find(|candidate| !final_path(candidate).exists() && !candidate.exists())
+                .expect("unbounded search finds a free name");
+            Ok((renamed, Decision::Renamed))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_existing() {
+        let dir = std::env::temp_dir().join(format!("video_dl_collision_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Title.mp4");
+        let same = |p: &Path| p.to_path_buf();
+
+        assert_eq!(resolve(path.clone(), OnExisting::Fail, same).unwrap(), (path.clone(), Decision::New));
+
+        std::fs::write(&path, b"").unwrap();
+        std::fs::write(dir.join("Title (1).mp4"), b"").unwrap();
+        assert_eq!(resolve(path.clone(), OnExisting::Skip, same).unwrap(), (path.clone(), Decision::Skipped));
+        assert_eq!(resolve(path.clone(), OnExisting::Overwrite, same).unwrap(), (path.clone(), Decision::Overwritten));
+        assert_eq!(resolve(path.clone(), OnExisting::Rename, same).unwrap(), (dir.join("Title (2).mp4"), Decision::Renamed));
+        assert!(matches!(resolve(path.clone(), OnExisting::Fail, same), Err(Error::FileExists(_))));
+
+        // Only the final file counts when post-processing renames the download
+        let audio = |p: &Path| p.with_extension("mp3");
+        assert_eq!(resolve(path.clone(), OnExisting::Skip, audio).unwrap(), (path.clone(), Decision::New));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,77 +1,267 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::time::{SystemTime, UNIX_EPOCH};
 use futures_util::future::join_all;
 use tokio::task;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use log::{info, warn};
 
-use crate::{Result, Error, Config, Downloader};
+use crate::{Result, Error, Downloader, InfoJson, PlaylistRange, VideoInfo};
+use crate::collision::{self, Decision};
+use crate::info_json::ToolVersions;
+use crate::template::TemplateContext;
+use crate::utils::dependency_check;
+use super::{DownloadOptions, DownloadSettings};
+use super::journal::{BatchState, Journal, Status};
 
-/// Helper function to handle downloading a single video within the batch
+/// What became of a video of a command
+#[derive(Debug, Clone)]
+pub enum Downloaded {
+    /// Saved to `path`, or skipped because that file already exists
+    File {
+        path: PathBuf,
+        decision: Decision,
+        /// Platform and ID of the video, for the download archive
+        platform: String,
+        id: Option<String>,
+    },
+    /// Skipped because the download archive lists the video
+    Archived { platform: String, id: String },
+}
+
+impl Downloaded {
+    /// Whether nothing was downloaded because the video is already there
+    pub fn is_skipped(&self) -> bool {
+        matches!(self, Downloaded::Archived { .. } | Downloaded::File { decision: Decision::Skipped, .. })
+    }
+
+    /// The path of the file, with a note when an existing file was in the way
+    pub fn describe(&self) -> String {
+        match self {
+            Downloaded::File { path, decision, .. } => match decision.note() {
+                Some(note) => format!("{:?} ({})", path, note),
+                None => format!("{:?}", path),
+            },
+            Downloaded::Archived { platform, id } => {
+                format!("{} {} (already in download archive)", platform.to_lowercase(), id)
+            },
+        }
+    }
+}
+
+/// Helper function to handle downloading a single video within the batch.
+///
+/// The file is named after the output template, inside `output_dir` if one is given;
+/// `index` is the 1-based position of the video for `{index}`. Videos in the download
+/// archive are skipped, without extracting them when the URL tells which video it is.
 pub async fn download_single_video(
     downloader: &Downloader,
     url: &str,
     output_dir: Option<PathBuf>,
-    quality: &str,
-    format: &str
-) -> Result<PathBuf> {
+    settings: &DownloadSettings,
+    index: Option<usize>
+) -> Result<Downloaded> {
+    if let Some(downloaded) = archived(downloader, url, settings)? {
+        return Ok(downloaded);
+    }
+    
     // Get video info
     let info = downloader.get_video_info(url).await?;
     
-    // Format selection logic
-    let mut selected_format = info.formats.iter()
-        .find(|f| {
-            f.format.to_string().eq_ignore_ascii_case(format) &&
-            f.quality.to_string().eq_ignore_ascii_case(quality)
-        });
-            
-    // If exact match not found, try to find a format with the requested quality
-    if selected_format.is_none() {
-        selected_format = info.formats.iter()
-            .find(|f| f.quality.to_string().eq_ignore_ascii_case(quality));
-    }
+    let format_id = settings.selector.select_id(&info.formats)?;
+
+    let context = TemplateContext { format_id: &format_id, index };
+    let output = downloader.output_path(&info, &context, output_dir.as_deref())?;
     
-    // If still not found, find closest quality with the requested format
-    if selected_format.is_none() && !quality.eq_ignore_ascii_case("best") {
-        selected_format = info.formats.iter()
-            .find(|f| f.format.to_string().eq_ignore_ascii_case(format));
+    download_to(downloader, &info, &format_id, output, settings, url).await
+}
+
+/// Archive entry of the video a URL names, if it can be read from the URL alone
+pub fn url_video(downloader: &Downloader, url: &str) -> Option<(String, String)> {
+    downloader.video_id(url).ok().flatten().map(|(platform, id)| (platform.to_string(), id))
+}
+
+/// Check the download archive for the video of a URL, if the URL names one
+pub fn archived(downloader: &Downloader, url: &str, settings: &DownloadSettings) -> Result<Option<Downloaded>> {
+    if settings.archive.is_none() || settings.force {
+        return Ok(None);
     }
     
-    // If still not found, just use the "best" format which is always available
-    let selected_format = selected_format
-        .or_else(|| info.formats.iter().find(|f| f.id == "best"))
-        .or_else(|| info.formats.first())
-        .ok_or_else(|| Error::NoSuitableFormats)?;
+    Ok(match downloader.video_id(url)? {
+        Some((platform, id)) if settings.in_archive(platform, &id) => {
+            Some(Downloaded::Archived { platform: platform.to_string(), id })
+        },
+        _ => None,
+    })
+}
 
-    // Prepare output path if directory is specified
-    let output = match output_dir {
+/// Download an extracted video to `output` and post-process it, first checking the
+/// download archive and applying the strategy for existing files. Finished downloads
+/// are added to the archive.
+pub async fn download_to(
+    downloader: &Downloader,
+    info: &VideoInfo,
+    format_id: &str,
+    output: PathBuf,
+    settings: &DownloadSettings,
+    source_url: &str
+) -> Result<Downloaded> {
+    let DownloadSettings { post, on_existing, archive, .. } = settings;
+    
+    if let Some(id) = &info.id {
+        if settings.in_archive(&info.platform, id) {
+            return Ok(Downloaded::Archived { platform: info.platform.clone(), id: id.clone() });
+        }
+    }
+    
+    let (output, decision) = collision::resolve(output, *on_existing, |path| post.final_path(path))?;
+    let downloaded = |path| Downloaded::File {
+        path,
+        decision,
+        platform: info.platform.clone(),
+        id: info.id.clone(),
+    };
+    
+    // An existing file is only replaced once its successor is complete, so that a failed
+    // download keeps it: the new file and its sidecars are made in a directory of their own
+    let staging = match decision {
+        Decision::Skipped => return Ok(downloaded(output)),
+        Decision::Overwritten => Some(staging_dir(&output).await?),
+        Decision::New | Decision::Renamed => None,
+    };
+    
+    let path = match &staging {
         Some(dir) => {
-            let filename = format!("{}.mp4", sanitize_filename::sanitize(&info.title));
-            Some(dir.join(filename))
+            let staged = dir.join(output.file_name().unwrap_or_default());
+            let result = match download_and_process(downloader, info, format_id, staged, settings).await {
+                Ok(path) => replace_from(dir, &path, &output).await,
+                Err(e) => Err(e),
+            };
+            let _ = tokio::fs::remove_dir_all(dir).await;
+            result?
         },
-        None => None
+        None => download_and_process(downloader, info, format_id, output, settings).await?,
     };
     
-    // Download the video and return the output path
+    if post.write_info_json {
+        let versions = settings.tool_versions.get_or_init(|| ToolVersions::detect(downloader.config())).await;
+        // The video is already downloaded, so a missing record doesn't fail it
+        if let Err(e) = write_info_json(info, format_id, source_url, &path, versions).await {
+            warn!("Failed to write video information for {:?}: {}", path, e);
+        }
+    }
+    
+    if let Some(archive) = archive {
+        if let Some(id) = &info.id {
+            archive.record(&info.platform, id)?;
+        }
+        // Also under the ID in the URL when it names another post, e.g. a Reddit
+        // crosspost or link post, so that the next run skips it without extracting
+        if let Some((platform, id)) = url_video(downloader, source_url) {
+            archive.record(&platform, &id)?;
+        }
+    }
+    Ok(downloaded(path))
+}
+
+/// Download a video to `output` and post-process it, returning the path of the final file
+async fn download_and_process(
+    downloader: &Downloader,
+    info: &VideoInfo,
+    format_id: &str,
+    output: PathBuf,
+    settings: &DownloadSettings
+) -> Result<PathBuf> {
+    let DownloadSettings { post, section, .. } = settings;
+    
     // Use the format ID directly instead of constructing a complex format specification
-    downloader.download(url, &selected_format.id, output).await
+    let section = section.resolve(info);
+    let path = downloader.download_info(info, format_id, Some(output), section.as_ref()).await?;
+    
+    // Chapters are embedded and split at their times in the downloaded file
+    match &section {
+        Some(section) => {
+            let clipped = VideoInfo { chapters: section.clip_chapters(&info.chapters), ..info.clone() };
+            post.run(&clipped, path).await
+        },
+        None => post.run(info, path).await,
+    }
+}
+
+/// Empty directory next to `output` to make its replacement in
+async fn staging_dir(output: &Path) -> Result<PathBuf> {
+    let name = output.file_name().unwrap_or_default().to_string_lossy();
+    let dir = output.with_file_name(format!(".{}.video-dl", name));
+    // Left over from an interrupted run
+    let _ = tokio::fs::remove_dir_all(&dir).await;
+    tokio::fs::create_dir_all(&dir).await?;
+    Ok(dir)
+}
+
+/// Move the files made in `staging` next to `output`, replacing the ones they share a
+/// name with, and return where `path` ends up
+async fn replace_from(staging: &Path, path: &Path, output: &Path) -> Result<PathBuf> {
+    let mut entries = tokio::fs::read_dir(staging).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        tokio::fs::rename(entry.path(), output.with_file_name(entry.file_name())).await?;
+    }
+    Ok(output.with_file_name(path.file_name().unwrap_or_default()))
+}
+
+/// Record a finished download in a `.info.json` file next to it
+pub async fn write_info_json(
+    info: &VideoInfo,
+    format_id: &str,
+    url: &str,
+    path: &Path,
+    versions: &ToolVersions
+) -> Result<()> {
+    let record = InfoJson::new(info, format_id, url, versions);
+    let json_path = record.write(path).await?;
+    info!("Video information written to {:?}", json_path);
+    Ok(())
+}
+
+/// Expand playlist, channel and profile URLs into the URLs of their videos.
+///
+/// URLs that fail to expand are reported and dropped; plain video URLs are kept as is.
+pub async fn expand_urls(downloader: &Downloader, urls: Vec<String>, range: &PlaylistRange) -> Vec<String> {
+    let mut expanded = Vec::new();
+    
+    for url in urls {
+        match downloader.is_collection(&url) {
+            Ok(true) => {
+                println!("Expanding playlist {}...", url);
+                match downloader.expand(&url, range).await {
+                    Ok(entries) => {
+                        println!("Found {} videos in {}", entries.len(), url);
+                        expanded.extend(entries.into_iter().map(|entry| entry.url.to_string()));
+                    },
+                    Err(e) => println!("✗ Failed to expand {}: {}", url, e),
+                }
+            },
+            _ => expanded.push(url),
+        }
+    }
+    
+    expanded
 }
 
-/// Handles batch download command execution
+/// Handles batch download command execution.
+///
+/// The state of every URL is kept in a journal at `state_path`, by default a
+/// `video-dl-batch-<time>.json` file in the output directory, for `--resume`.
 pub async fn batch_download_command(
     urls: Vec<String>,
     file_path: Option<PathBuf>,
     output_dir: Option<PathBuf>,
-    quality: String,
-    format: String,
-    parallel: bool
+    options: DownloadOptions,
+    parallel: bool,
+    state_path: Option<PathBuf>
 ) -> Result<()> {
     // Load configuration
-    let config = Config::load();
-    
-    // Use provided quality/format or fall back to config defaults
-    let quality = if quality == "best" { config.default_quality.clone() } else { quality };
-    let format = if format == "mp4" { config.default_format.clone() } else { format };
+    let config = options.load_config()?;
     
     // Create downloader
     let downloader = Downloader::with_config(config.clone());
@@ -97,21 +287,81 @@ pub async fn batch_download_command(
         }
     }
     
+    // Fan playlists, channels and profiles out into individual videos
+    let all_urls = expand_urls(&downloader, all_urls, &options.playlist_range()).await;
+    
     if all_urls.is_empty() {
         return Err(Error::InvalidArgument("No URLs provided for download".into()));
     }
     
-    println!("Starting batch download of {} videos", all_urls.len());
+    let state_path = match state_path {
+        Some(path) => path,
+        None => {
+            let dir = output_dir.clone().unwrap_or_else(|| config.download_dir.clone());
+            std::fs::create_dir_all(&dir)?;
+            let started = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+            dir.join(format!("video-dl-batch-{}.json", started))
+        }
+    };
+    let journal = Journal::create(&state_path, BatchState::new(all_urls, output_dir, options, parallel))?;
+    println!("Batch state is saved to {:?}; continue an interrupted batch with --resume", journal.path());
+    
+    run_batch(&journal, false).await
+}
+
+/// Continues a batch from its state file, with the output directory and options it was
+/// started with. Pending and interrupted downloads are attempted again, and failed ones
+/// too if `retry_failed` is set.
+pub async fn resume_batch_command(state_path: PathBuf, retry_failed: bool) -> Result<()> {
+    let journal = Journal::load(&state_path)?;
+    run_batch(&journal, retry_failed).await
+}
+
+/// Download the remaining entries of a batch journal, recording each outcome in it
+async fn run_batch(journal: &Journal, retry_failed: bool) -> Result<()> {
+    let BatchState { output_dir, parallel, options, entries } = journal.state();
+    
+    let config = options.load_config()?;
+    let settings = options.settings(&config)?;
+    
+    // Checked here rather than up front, as a resumed batch uses the options it was started with
+    if options.extract_audio {
+        let status = dependency_check::check_dependencies(&config).await;
+        dependency_check::require_ffmpeg(&status, "extracting audio")?;
+    }
+    
+    let downloader = Downloader::with_config(config);
+    
+    let remaining = journal.remaining(retry_failed);
+    if remaining.is_empty() {
+        println!("Nothing left to download in {:?}", journal.path());
+        return Ok(());
+    }
+    
+    if remaining.len() < entries.len() {
+        println!("Resuming batch download: {} of {} videos left", remaining.len(), entries.len());
+    } else {
+        println!("Starting batch download of {} videos", remaining.len());
+    }
+    
+    // Summarize results
+    let mut success_count = 0;
+    let mut skipped_count = 0;
+    let mut failure_count = 0;
     
     if parallel {
         // Process URLs in parallel
         let multi_progress = MultiProgress::new();
         
-        let download_tasks = all_urls.into_iter().map(|url| {
+        let download_tasks = remaining.into_iter().map(|(index, entry)| {
             let downloader_clone = downloader.clone();
-            let quality = quality.clone();
-            let format = format.clone();
+            let settings = match entry.status {
+                Status::Running => settings.for_interrupted(),
+                _ => settings.clone(),
+            };
             let output_dir = output_dir.clone();
+            let journal = journal.clone();
+            let url = entry.url;
             let progress_bar = multi_progress.add(ProgressBar::new(100));
             progress_bar.set_style(
                 ProgressStyle::default_bar()
@@ -121,13 +371,19 @@ pub async fn batch_download_command(
             progress_bar.set_message(format!("Downloading: {}", url));
             
             task::spawn(async move {
-                let result = match downloader_clone.get_video_info(&url).await {
-                    Ok(info) => {
-                        progress_bar.set_message(format!("Downloading: {}", info.title));
-                        download_single_video(&downloader_clone, &url, output_dir, &quality, &format).await
+                journal.start(index);
+                let result = match archived(&downloader_clone, &url, &settings) {
+                    Ok(Some(downloaded)) => Ok(downloaded),
+                    Ok(None) => match downloader_clone.get_video_info(&url).await {
+                        Ok(info) => {
+                            progress_bar.set_message(format!("Downloading: {}", info.title));
+                            download_single_video(&downloader_clone, &url, output_dir, &settings, Some(index + 1)).await
+                        },
+                        Err(e) => Err(e)
                     },
                     Err(e) => Err(e)
                 };
+                journal.finish(index, &result);
                 
                 if result.is_ok() {
                     progress_bar.finish_with_message(format!("Completed: {}", url));
@@ -142,16 +398,16 @@ pub async fn batch_download_command(
         // Wait for all downloads to complete
         let results = join_all(download_tasks).await;
         
-        // Summarize results
-        let mut success_count = 0;
-        let mut failure_count = 0;
-        
         println!("\nBatch download summary:");
         
         for result in results {
             match result {
-                Ok((url, Ok(path))) => {
-                    println!("✓ Successfully downloaded: {} -> {:?}", url, path);
+                Ok((url, Ok(downloaded))) if downloaded.is_skipped() => {
+                    println!("- Skipped: {} -> {}", url, downloaded.describe());
+                    skipped_count += 1;
+                },
+                Ok((url, Ok(downloaded))) => {
+                    println!("✓ Successfully downloaded: {} -> {}", url, downloaded.describe());
                     success_count += 1;
                 },
                 Ok((url, Err(e))) => {
@@ -164,18 +420,31 @@ pub async fn batch_download_command(
                 }
             }
         }
-        
-        println!("\nBatch download complete: {} successful, {} failed", success_count, failure_count);
     } else {
         // Process URLs sequentially
-        let mut success_count = 0;
-        let mut failure_count = 0;
-        
-        for url in all_urls {
-            println!("Downloading {}... ", url);
-            match download_single_video(&downloader, &url, output_dir.clone(), &quality, &format).await {
-                Ok(path) => {
-                    println!("✓ Success: {:?}", path);
+        for (index, entry) in remaining {
+            let settings = match entry.status {
+                Status::Running => {
+                    println!("Resuming interrupted download of {}... ", entry.url);
+                    settings.for_interrupted()
+                },
+                _ => {
+                    println!("Downloading {}... ", entry.url);
+                    settings.clone()
+                },
+            };
+            
+            journal.start(index);
+            let result = download_single_video(&downloader, &entry.url, output_dir.clone(), &settings, Some(index + 1)).await;
+            journal.finish(index, &result);
+            
+            match result {
+                Ok(downloaded) if downloaded.is_skipped() => {
+                    println!("- Skipped: {}", downloaded.describe());
+                    skipped_count += 1;
+                },
+                Ok(downloaded) => {
+                    println!("✓ Success: {}", downloaded.describe());
                     success_count += 1;
                 },
                 Err(e) => {
@@ -184,9 +453,12 @@ pub async fn batch_download_command(
                 }
             }
         }
-        
-        println!("\nBatch download complete: {} successful, {} failed", success_count, failure_count);
+    }
+    
+    println!("\nBatch download complete: {} successful, {} skipped, {} failed", success_count, skipped_count, failure_count);
+    if failure_count > 0 {
+        println!("Retry the failed downloads with: video-dl batch --resume {:?} --retry-failed", journal.path());
     }
     
     Ok(())
-}
\ No newline at end of file
+}
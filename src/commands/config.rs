@@ -1,4 +1,6 @@
 use crate::{Result, Config};
+use crate::collision::OnExisting;
+use crate::template::OutputTemplate;
 use std::str::FromStr;
 use std::path::PathBuf;
 use super::ConfigAction;
@@ -18,7 +20,11 @@ pub async fn config_command(action: Option<ConfigAction>) -> Result<()> {
                     "default_format" => println!("default_format: {}", config.default_format),
                     "show_progress" => println!("show_progress: {}", config.show_progress),
                     "overwrite_files" => println!("overwrite_files: {}", config.overwrite_files),
+                    "on_existing" => println!("on_existing: {}", config.existing_files()),
+                    "connections" => println!("connections: {}", config.connections),
                     "ytdlp_path" => println!("ytdlp_path: {:?}", config.ytdlp_path),
+                    "output_template" => println!("output_template: {}", config.output_template),
+                    "archive" => println!("archive: {:?}", config.archive),
                     _ => println!("Unknown configuration key: {}", key),
                 }
             } else {
@@ -29,6 +35,13 @@ pub async fn config_command(action: Option<ConfigAction>) -> Result<()> {
                 println!("  default_format: {}", config.default_format);
                 println!("  show_progress: {}", config.show_progress);
                 println!("  overwrite_files: {}", config.overwrite_files);
+                println!("  on_existing: {}", config.existing_files());
+                println!("  connections: {}", config.connections);
+                println!("  output_template: {}", config.output_template);
+                match &config.archive {
+                    Some(path) => println!("  archive: {:?}", path),
+                    None => println!("  archive: None"),
+                }
                 if let Some(path) = &config.ytdlp_path {
                     println!("  ytdlp_path: {:?}", path);
                 } else {
@@ -68,6 +81,47 @@ pub async fn config_command(action: Option<ConfigAction>) -> Result<()> {
                         println!("Invalid value for overwrite_files. Use 'true' or 'false'");
                     }
                 },
+                "on_existing" => {
+                    if value.to_lowercase() == "none" {
+                        config.on_existing = None;
+                        println!("Cleared on_existing, will follow overwrite_files");
+                    } else {
+                        match OnExisting::from_str(&value) {
+                            Ok(val) => {
+                                config.on_existing = Some(val);
+                                println!("Updated on_existing to {}", val);
+                            },
+                            Err(e) => println!("{}", e),
+                        }
+                    }
+                },
+                "output_template" => {
+                    match OutputTemplate::parse(&value) {
+                        Ok(_) => {
+                            config.output_template = value;
+                            println!("Updated output_template to {}", config.output_template);
+                        },
+                        Err(e) => println!("{}", e),
+                    }
+                },
+                "connections" => {
+                    match usize::from_str(&value) {
+                        Ok(val) if val > 0 => {
+                            config.connections = val;
+                            println!("Updated connections to {}", config.connections);
+                        },
+                        _ => println!("Invalid value for connections. Use a positive number"),
+                    }
+                },
+                "archive" => {
+                    if value.to_lowercase() == "none" {
+                        config.archive = None;
+                        println!("Cleared archive, downloads will not be recorded");
+                    } else {
+                        config.archive = Some(PathBuf::from(&value));
+                        println!("Updated archive to {:?}", config.archive);
+                    }
+                },
                 "ytdlp_path" => {
                     if value.to_lowercase() == "none" {
                         config.ytdlp_path = None;
@@ -80,7 +134,7 @@ pub async fn config_command(action: Option<ConfigAction>) -> Result<()> {
                 },
                 _ => {
                     println!("Unknown configuration key: {}", key);
-                    println!("Available keys: download_dir, default_quality, default_format, show_progress, overwrite_files, ytdlp_path");
+                    println!("Available keys: download_dir, default_quality, default_format, show_progress, overwrite_files, on_existing, connections, output_template, archive, ytdlp_path");
                     return Ok(());
                 }
             }
@@ -116,6 +170,10 @@ pub async fn config_command(action: Option<ConfigAction>) -> Result<()> {
             println!("  default_format    - Default video format (mp4, webm, etc.)");
             println!("  show_progress     - Whether to show progress bars (true/false)");
             println!("  overwrite_files   - Whether to overwrite existing files (true/false)");
+            println!("  on_existing       - What to do with existing files (skip, overwrite, rename, fail), or 'none' to follow overwrite_files");
+            println!("  connections       - Parallel connections for direct HTTP downloads (1 disables splitting)");
+            println!("  output_template   - File name template, e.g. {{uploader}}/{{title}}.{{ext}}");
+            println!("  archive           - Download archive file of videos to skip, or 'none'");
             println!("  ytdlp_path        - Path to yt-dlp executable, or 'none' to use system PATH");
         }
     }
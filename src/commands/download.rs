@@ -1,57 +1,141 @@
 use std::path::PathBuf;
-use crate::{Error, Result, Downloader, Config};
+use crate::{Error, Result, Downloader, InfoJson};
+use super::{DownloadOptions, DownloadSettings};
+use crate::template::TemplateContext;
+use super::batch::{archived, download_single_video, download_to, Downloaded};
 
 /// Handles the download command execution
 pub async fn download_command(
     url: String, 
     output: Option<PathBuf>, 
-    quality: String, 
-    format: String
+    options: DownloadOptions
 ) -> Result<()> {
     // Load configuration
-    let config = Config::load();
+    let config = options.load_config()?;
     
-    // Use provided quality/format or fall back to config defaults
-    let quality = if quality == "best" { config.default_quality.clone() } else { quality };
-    let format = if format == "mp4" { config.default_format.clone() } else { format };
+    let settings = options.settings(&config)?;
     
     // Create downloader with config
     let downloader = Downloader::with_config(config);
     
+    // Playlists, channels and profiles are downloaded entry by entry into a directory
+    if downloader.is_collection(&url)? {
+        return download_collection(&downloader, &url, output, &options, &settings).await;
+    }
+    
+    if let Some(downloaded) = archived(&downloader, &url, &settings)? {
+        report(&downloaded);
+        return Ok(());
+    }
+    
     println!("Fetching video information...");
     let info = downloader.get_video_info(&url).await?;
     
-    // Format selection logic
-    let mut selected_format = info.formats.iter()
-        .find(|f| {
-            f.format.to_string().eq_ignore_ascii_case(&format) &&
-            f.quality.to_string().eq_ignore_ascii_case(&quality)
-        });
-            
-    // If exact match not found, try to find a format with the requested quality
-    if selected_format.is_none() {
-        selected_format = info.formats.iter()
-            .find(|f| f.quality.to_string().eq_ignore_ascii_case(&quality));
+    let format_id = settings.selector.select_id(&info.formats)?;
+    let primary_id = format_id.split('+').next().unwrap_or(&format_id);
+    let selected = info.formats.iter().find(|f| f.id == primary_id);
+
+    match selected {
+        Some(format) => println!("Starting download of '{}' in {} {} (format ID: {})",
+            info.title, format.quality, format.format, format_id
+        ),
+        None => println!("Starting download of '{}' (format ID: {})", info.title, format_id),
     }
+
+    // An explicit output path is used as given, otherwise the file is named after the template
+    let output = match output {
+        Some(path) => path,
+        None => downloader.output_path(&info, &TemplateContext { format_id: &format_id, index: None }, None)?,
+    };
+    let downloaded = download_to(&downloader, &info, &format_id, output, &settings, &url).await?;
     
-    // If still not found, find closest quality with the requested format
-    if selected_format.is_none() && !quality.eq_ignore_ascii_case("best") {
-        selected_format = info.formats.iter()
-            .find(|f| f.format.to_string().eq_ignore_ascii_case(&format));
-    }
+    report(&downloaded);
+    Ok(())
+}
+
+/// Downloads a video recorded in a `.info.json` file without extracting it again.
+///
+/// The recorded format is downloaded unless `--select` asks for another one.
+pub async fn download_info_json_command(
+    path: PathBuf,
+    output: Option<PathBuf>,
+    options: DownloadOptions
+) -> Result<()> {
+    let config = options.load_config()?;
     
-    // If still not found, just pick the first format (best quality)
-    let selected_format = selected_format
-        .or_else(|| info.formats.first())
-        .ok_or_else(|| Error::NoSuitableFormats)?;
+    let record = InfoJson::load(&path).await?;
+    let info = record.info;
+    let settings = options.settings(&config)?;
+    
+    let format_id = match &options.select {
+        Some(_) => settings.selector.select_id(&info.formats)?,
+        None => record.format_id,
+    };
+    
+    let downloader = Downloader::with_config(config);
+    
+    println!("Starting download of '{}' (format ID: {})", info.title, format_id);
+    let output = match output {
+        Some(path) => path,
+        None => downloader.output_path(&info, &TemplateContext { format_id: &format_id, index: None }, None)?,
+    };
+    let downloaded = download_to(&downloader, &info, &format_id, output, &settings, &record.source_url).await?;
+    
+    report(&downloaded);
+    Ok(())
+}
 
-    println!("Starting download of '{}' in {} {} (format ID: {})", 
-        info.title, selected_format.quality, selected_format.format, selected_format.id
-    );
+/// Print the outcome of a single download
+fn report(downloaded: &Downloaded) {
+    if downloaded.is_skipped() {
+        println!("\n- Skipped download: {}", downloaded.describe());
+    } else {
+        println!("\n✓ Download completed: {}", downloaded.describe());
+    }
+}
 
-    // Download returns the path where the file was saved
-    let output_path = downloader.download(&url, &selected_format.id, output).await?;
+/// Downloads every selected entry of a collection URL into the output directory
+async fn download_collection(
+    downloader: &Downloader,
+    url: &str,
+    output_dir: Option<PathBuf>,
+    options: &DownloadOptions,
+    settings: &DownloadSettings
+) -> Result<()> {
+    println!("Fetching playlist entries...");
+    let entries = downloader.expand(url, &options.playlist_range()).await?;
+    
+    if entries.is_empty() {
+        return Err(Error::InvalidArgument("Playlist contains no videos in the selected range".into()));
+    }
+    
+    let output_dir = output_dir.unwrap_or_else(|| downloader.config().download_dir.clone());
+    std::fs::create_dir_all(&output_dir)?;
+    
+    println!("Downloading {} videos into {:?}", entries.len(), output_dir);
+    
+    let total = entries.len();
+    let mut skipped_count = 0;
+    let mut failure_count = 0;
+    
+    for (index, entry) in entries.iter().enumerate() {
+        let label = entry.title.as_deref().unwrap_or(entry.url.as_str());
+        println!("[{}/{}] Downloading {}...", index + 1, total, label);
+        
+        match download_single_video(downloader, entry.url.as_str(), Some(output_dir.clone()), settings, Some(index + 1)).await {
+            Ok(downloaded) if downloaded.is_skipped() => {
+                println!("- Skipped: {}", downloaded.describe());
+                skipped_count += 1;
+            },
+            Ok(downloaded) => println!("✓ Success: {}", downloaded.describe()),
+            Err(e) => {
+                println!("✗ Failed: {}", e);
+                failure_count += 1;
+            }
+        }
+    }
     
-    println!("\n✓ Download completed: {:?}", output_path);
+    println!("\nPlaylist download complete: {} successful, {} skipped, {} failed",
+        total - skipped_count - failure_count, skipped_count, failure_count);
     Ok(())
-}
\ No newline at end of file
+}
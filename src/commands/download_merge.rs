@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs::File;
 use std::io::{BufRead, BufReader, Write};
 use tokio::process::Command;
@@ -6,23 +6,23 @@ use indicatif::{ProgressBar, ProgressStyle};
 use futures_util::future::join_all;
 use tokio::task;
 use uuid::Uuid;
-use sanitize_filename;
 
-use crate::{Result, Error, Config, Downloader};
+use crate::{Result, Error, Downloader};
+use crate::collision::{self, Decision};
 use crate::utils::dependency_check;
-use crate::commands::batch::download_single_video;
+use crate::commands::batch::{download_single_video, expand_urls, url_video, Downloaded};
+use super::{DownloadOptions, DownloadSettings};
 
 /// Downloads multiple videos and automatically merges them into a single output file
 pub async fn download_merge_command(
     urls: Vec<String>,
     file_path: Option<PathBuf>,
     output: PathBuf,
-    quality: String,
-    format: String,
+    options: DownloadOptions,
     parallel: bool
 ) -> Result<()> {
     // Check dependencies
-    let config = Config::load();
+    let config = options.load_config()?;
     let status = dependency_check::check_dependencies(&config).await;
     
     if !status.ffmpeg_available {
@@ -32,6 +32,16 @@ pub async fn download_merge_command(
         });
     }
 
+    // Check the merged file before downloading anything for it
+    let (output, decision) = collision::resolve(output, config.existing_files(), Path::to_path_buf)?;
+    if decision == Decision::Skipped {
+        println!("{} already exists, skipping download-merge", output.display());
+        return Ok(());
+    }
+    if let Some(note) = decision.note() {
+        println!("Output file: {} ({})", output.display(), note);
+    }
+    
     // Create temporary directory for downloaded videos
     let temp_dir = std::env::temp_dir().join(format!("video_dl_merge_{}", Uuid::new_v4()));
     std::fs::create_dir_all(&temp_dir)
@@ -39,9 +49,12 @@ pub async fn download_merge_command(
     
     println!("Using temporary directory: {}", temp_dir.display());
     
-    // Use provided quality/format or fall back to config defaults
-    let quality = if quality == "best" { config.default_quality.clone() } else { quality };
-    let format = if format == "mp4" { config.default_format.clone() } else { format };
+    let settings = options.settings(&config)?;
+    
+    // The merge needs every video, so the archive is checked and updated for the set as a whole
+    let archive = settings.archive.clone();
+    
+    let settings = DownloadSettings { archive: None, ..settings };
     
     // Create downloader
     let downloader = Downloader::with_config(config.clone());
@@ -67,6 +80,9 @@ pub async fn download_merge_command(
         }
     }
     
+    // Fan playlists, channels and profiles out into individual videos
+    let all_urls = expand_urls(&downloader, all_urls, &options.playlist_range()).await;
+    
     if all_urls.is_empty() {
         return Err(Error::InvalidArgument("No URLs provided for download".into()));
     }
@@ -75,10 +91,23 @@ pub async fn download_merge_command(
         return Err(Error::InvalidArgument("At least two URLs are required for download-merge".into()));
     }
     
+    if let Some(archive) = archive.as_ref().filter(|_| !settings.force) {
+        let all_archived = all_urls.iter().all(|url| matches!(
+            downloader.video_id(url),
+            Ok(Some((platform, id))) if archive.contains(platform, &id)
+        ));
+        if all_archived {
+            println!("All {} videos are in the download archive, skipping download-merge", all_urls.len());
+            let _ = std::fs::remove_dir_all(&temp_dir);
+            return Ok(());
+        }
+    }
+    
     println!("Step 1/2: Downloading {} videos", all_urls.len());
     
-    // Track downloaded files to merge later
+    // Track downloaded files to merge later, and the videos to archive once merged
     let mut downloaded_files = Vec::new();
+    let mut videos = Vec::new();
     
     if parallel {
         // Process URLs in parallel
@@ -86,8 +115,7 @@ pub async fn download_merge_command(
         
         let download_tasks = all_urls.into_iter().enumerate().map(|(index, url)| {
             let downloader_clone = downloader.clone();
-            let quality = quality.clone();
-            let format = format.clone();
+            let settings = settings.clone();
             let temp_dir = temp_dir.clone();
             let progress_bar = multi_progress.add(ProgressBar::new(100));
             progress_bar.set_style(
@@ -98,10 +126,12 @@ pub async fn download_merge_command(
             progress_bar.set_message(format!("Downloading: {}", url));
             
             task::spawn(async move {
-                let output_dir = Some(temp_dir);
-                let result = download_single_video(&downloader_clone, &url, output_dir, &quality, &format).await;
+                let output_dir = Some(video_dir(&temp_dir, index));
+                let result = download_single_video(&downloader_clone, &url, output_dir, &settings, Some(index + 1)).await
+                    .and_then(merge_input)
+                    .map(|(path, video)| (path, video.into_iter().chain(url_video(&downloader_clone, &url)).collect::<Vec<_>>()));
                 
-                if let Ok(path) = &result {
+                if let Ok((path, _)) = &result {
                     progress_bar.finish_with_message(format!("Downloaded: {}", path.file_name().unwrap_or_default().to_string_lossy()));
                 } else {
                     progress_bar.abandon_with_message(format!("Failed: {}", url));
@@ -118,11 +148,10 @@ pub async fn download_merge_command(
         // Process results while maintaining order
         let mut downloaded_files_with_index: Vec<(usize, PathBuf)> = Vec::new();
         
-        for result in results {
-            if let Ok((index, download_result)) = result {
-                if let Ok(path) = download_result {
-                    downloaded_files_with_index.push((index, path));
-                }
+        for (index, download_result) in results.into_iter().flatten() {
+            if let Ok((path, video)) = download_result {
+                downloaded_files_with_index.push((index, path));
+                videos.extend(video);
             }
         }
         
@@ -135,12 +164,14 @@ pub async fn download_merge_command(
             .collect();
     } else {
         // Process URLs sequentially
-        for url in all_urls {
+        for (index, url) in all_urls.into_iter().enumerate() {
             println!("Downloading {}...", url);
-            match download_single_video(&downloader, &url, Some(temp_dir.clone()), &quality, &format).await {
-                Ok(path) => {
+            match download_single_video(&downloader, &url, Some(video_dir(&temp_dir, index)), &settings, Some(index + 1)).await.and_then(merge_input) {
+                Ok((path, video)) => {
                     println!("✓ Success: {}", path.file_name().unwrap_or_default().to_string_lossy());
                     downloaded_files.push(path);
+                    videos.extend(video);
+                    videos.extend(url_video(&downloader, &url));
                 },
                 Err(e) => {
                     println!("✗ Failed: {}", e);
@@ -157,10 +188,8 @@ pub async fn download_merge_command(
         });
     }
     
-    // Sort files by name to ensure consistent order (usually this will match the input order)
-    downloaded_files.sort_by(|a, b| {
-        a.file_name().unwrap_or_default().cmp(&b.file_name().unwrap_or_default())
-    });
+    // Sort files by path; the numbered directories keep them in input order
+    downloaded_files.sort();
     
     println!("Step 2/2: Merging {} downloaded videos into one file", downloaded_files.len());
     
@@ -194,7 +223,8 @@ pub async fn download_merge_command(
     
     // Run ffmpeg to concatenate the videos
     let mut cmd = Command::new("ffmpeg");
-    cmd.args(["-f", "concat", "-safe", "0", "-i"])
+    // Existing outputs have been dealt with above, so ffmpeg may replace the file
+    cmd.args(["-y", "-f", "concat", "-safe", "0", "-i"])
        .arg(&concat_file)
        .args(["-c", "copy"]) // Copy streams without re-encoding
        .arg(&output);
@@ -221,9 +251,33 @@ pub async fn download_merge_command(
     
     pb.finish_with_message(format!("Successfully merged {} videos into {}", downloaded_files.len(), output.display()));
     
+    if let Some(archive) = &archive {
+        for (platform, id) in &videos {
+            archive.record(platform, id)?;
+        }
+    }
+    
     // Clean up temporary directory
     println!("Cleaning up temporary files...");
     let _ = std::fs::remove_dir_all(&temp_dir);
     
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Directory of the video at `index` inside the temporary directory. Every video gets its
+/// own, so videos with the same name can't write to the same file even when downloaded in
+/// parallel, and the paths sort in input order.
+fn video_dir(temp_dir: &Path, index: usize) -> PathBuf {
+    temp_dir.join(format!("{:05}", index + 1))
+}
+
+/// The file of a video downloaded for merging, and its platform and ID for the archive
+fn merge_input(downloaded: Downloaded) -> Result<(PathBuf, Option<(String, String)>)> {
+    match downloaded {
+        Downloaded::File { path, platform, id, .. } => Ok((path, id.map(|id| (platform, id)))),
+        // Downloads for merging don't consult the archive
+        Downloaded::Archived { platform, id } => Err(Error::DownloadFailed {
+            reason: format!("{} {} is in the download archive", platform, id),
+        }),
+    }
+}
@@ -1,4 +1,5 @@
-use crate::{Result, Downloader, Config};
+use std::path::PathBuf;
+use crate::{Result, Downloader, Config, InfoJson, VideoInfo};
 
 /// Handles the info command execution
 pub async fn info_command(url: String) -> Result<()> {
@@ -9,7 +10,58 @@ pub async fn info_command(url: String) -> Result<()> {
     println!("Fetching video information...");
     let info = downloader.get_video_info(&url).await?;
     
+    print_info(&info);
+    
+    println!("\nTo download this video:");
+    println!("  video-dl download -u {} --select FORMAT_ID", url);
+    
+    Ok(())
+}
+
+/// Shows the video recorded in a `.info.json` file
+pub async fn info_json_command(path: PathBuf) -> Result<()> {
+    let record = InfoJson::load(&path).await?;
+    
+    println!("Source URL: {}", record.source_url);
+    println!("Downloaded format: {}", record.format_id);
+    print_info(&record.info);
+    
+    println!("\nTo download this video again:");
+    println!("  video-dl download --load-info-json {:?}", path);
+    
+    Ok(())
+}
+
+fn print_info(info: &VideoInfo) {
     println!("\nTitle: {}", info.title);
+    println!("Platform: {}", info.platform);
+    if let Some(id) = &info.id {
+        println!("Video ID: {}", id);
+    }
+    match (&info.uploader, &info.uploader_id) {
+        (Some(name), Some(id)) if name != id => println!("Uploader: {} ({})", name, id),
+        (Some(name), _) | (None, Some(name)) => println!("Uploader: {}", name),
+        (None, None) => {}
+    }
+    if let Some(date) = info.upload_date.as_deref().filter(|d| d.len() == 8) {
+        println!("Upload date: {}-{}-{}", &date[..4], &date[4..6], &date[6..]);
+    }
+    let counts: Vec<_> = [("views", info.view_count), ("likes", info.like_count), ("comments", info.comment_count)]
+        .into_iter()
+        .filter_map(|(label, count)| count.map(|c| format!("{} {}", c, label)))
+        .collect();
+    if !counts.is_empty() {
+        println!("Stats: {}", counts.join(", "));
+    }
+    if !info.tags.is_empty() {
+        println!("Tags: {}", info.tags.join(", "));
+    }
+    if !info.categories.is_empty() {
+        println!("Categories: {}", info.categories.join(", "));
+    }
+    if let Some(page) = &info.webpage_url {
+        println!("Page: {}", page);
+    }
     if let Some(desc) = &info.description {
         // Print only the first few lines of the description if it's long
         let desc_preview = desc.lines().take(5).collect::<Vec<_>>().join("\n");
@@ -27,6 +79,16 @@ pub async fn info_command(url: String) -> Result<()> {
         let seconds = duration % 60;
         println!("Duration: {:02}:{:02}:{:02}", hours, minutes, seconds);
     }
+    if let Some(section) = &info.section {
+        let end = section.end.map(format_time).unwrap_or_else(|| "end".to_string());
+        println!("Section in URL: {} - {} (download it with --url-section)", format_time(section.start), end);
+    }
+    if let Some(thumbnail) = info.best_thumbnail() {
+        match (thumbnail.width, thumbnail.height) {
+            (Some(width), Some(height)) => println!("Thumbnail: {} ({}x{})", thumbnail.url, width, height),
+            _ => println!("Thumbnail: {}", thumbnail.url),
+        }
+    }
     
     println!("\nAvailable formats:");
     
@@ -42,17 +104,56 @@ pub async fn info_command(url: String) -> Result<()> {
     for (quality, formats) in quality_groups.iter() {
         println!("\n{} quality:", quality);
         for format in formats {
+            let bitrate = match (format.video_bitrate, format.audio_bitrate) {
+                (Some(v), Some(a)) => format!("{} kbps video, {} kbps audio", v, a),
+                (Some(v), None) => format!("{} kbps", v),
+                (None, Some(a)) => format!("{} kbps audio", a),
+                (None, None) => "unknown".to_string(),
+            };
             println!(
-                "  - Format ID: {}\n    Format: {}\n    Size: {}\n",
+                "  - Format ID: {}\n    Format: {}\n    Streams: {}\n    Bitrate: {}\n    Size: {}\n",
                 format.id,
                 format.format,
+                format.stream_summary(),
+                bitrate,
                 format.file_size.map(|s| format!("{} bytes", s)).unwrap_or_else(|| "unknown".to_string())
             );
         }
     }
     
-    println!("\nTo download this video:");
-    println!("  video-dl download -u {} -f FORMAT_ID", url);
+    if !info.chapters.is_empty() {
+        println!("\nChapters:");
+        for chapter in &info.chapters {
+            println!("  {} - {}  {}", format_time(chapter.start), format_time(chapter.end), chapter.title);
+        }
+    }
     
-    Ok(())
-}
\ No newline at end of file
+    if !info.subtitles.is_empty() {
+        println!("\nSubtitles:");
+        for (label, auto_generated) in [("manual", false), ("auto-generated", true)] {
+            let languages: Vec<_> = info.subtitles.iter()
+                .filter(|track| track.auto_generated == auto_generated)
+                .map(|track| track.language.as_str())
+                .collect();
+            if languages.is_empty() {
+                continue;
+            }
+            
+            // Auto-translated captions can cover a hundred languages
+            let shown = languages.iter().take(20).copied().collect::<Vec<_>>().join(", ");
+            let more = languages.len().saturating_sub(20);
+            println!("  {}: {}{}", label, shown, if more > 0 { format!(" and {} more", more) } else { String::new() });
+        }
+    }
+}
+
+/// Format seconds as `H:MM:SS`, or `M:SS` below an hour
+fn format_time(seconds: f64) -> String {
+    let total = seconds.round() as u64;
+    let (hours, minutes, seconds) = (total / 3600, total % 3600 / 60, total % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
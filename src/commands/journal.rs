@@ -0,0 +1,183 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+use super::DownloadOptions;
+use super::batch::Downloaded;
+
+/// Where a URL of a batch stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pending,
+    /// Started but not finished; the batch was interrupted if this is still set on resume
+    Running,
+    Done,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub url: String,
+    pub status: Status,
+    /// Why the last attempt failed
+    pub error: Option<String>,
+    /// The downloaded file, or what was skipped
+    pub result: Option<String>,
+}
+
+/// Everything needed to continue a batch: its settings and the state of every URL,
+/// with playlists already expanded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchState {
+    pub output_dir: Option<PathBuf>,
+    pub parallel: bool,
+    pub options: DownloadOptions,
+    pub entries: Vec<Entry>,
+}
+
+impl BatchState {
+    pub fn new(urls: Vec<String>, output_dir: Option<PathBuf>, options: DownloadOptions, parallel: bool) -> Self {
+        let entries = urls.into_iter()
+            .map(|url| Entry { url, status: Status::Pending, error: None, result: None })
+            .collect();
+        Self { output_dir, parallel, options, entries }
+    }
+}
+
+/// A batch state file, rewritten after every change so that a batch that is killed can
+/// be resumed with `batch --resume`.
+///
+/// Clones share the same state, so parallel downloads can update one journal.
+#[derive(Debug, Clone)]
+pub struct Journal {
+    path: PathBuf,
+    state: Arc<Mutex<BatchState>>,
+}
+
+impl Journal {
+    /// Start a journal for a new batch at `path`
+    pub fn create(path: &Path, state: BatchState) -> Result<Self> {
+        let journal = Self { path: path.to_path_buf(), state: Arc::new(Mutex::new(state)) };
+        journal.save(&journal.state.lock().unwrap())?;
+        Ok(journal)
+    }
+
+    /// Open the journal of an earlier batch
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| Error::IoError(format!("Failed to read batch state {:?}: {}", path, e)))?;
+        let state = serde_json::from_str(&text)
+            .map_err(|e| Error::OutputParsing(format!("Invalid batch state {:?}: {}", path, e)))?;
+        Ok(Self { path: path.to_path_buf(), state: Arc::new(Mutex::new(state)) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn state(&self) -> BatchState {
+        self.state.lock().unwrap().clone()
+    }
+
+    /// Entries still to download with their positions: pending ones, interrupted ones
+    /// and, if `retry_failed` is set, failed ones
+    pub fn remaining(&self, retry_failed: bool) -> Vec<(usize, Entry)> {
+        self.state.lock().unwrap().entries.iter()
+            .enumerate()
+            .filter(|(_, entry)| match entry.status {
+                Status::Pending | Status::Running => true,
+                Status::Failed => retry_failed,
+                Status::Done => false,
+            })
+            .map(|(index, entry)| (index, entry.clone()))
+            .collect()
+    }
+
+    /// Mark an entry as being downloaded
+    pub fn start(&self, index: usize) {
+        self.update(index, |entry| {
+            entry.status = Status::Running;
+            entry.error = None;
+        });
+    }
+
+    /// Record how the download of an entry ended
+    pub fn finish(&self, index: usize, result: &Result<Downloaded>) {
+        self.update(index, |entry| match result {
+            Ok(downloaded) => {
+                entry.status = Status::Done;
+                entry.result = Some(downloaded.describe());
+            },
+            Err(e) => {
+                entry.status = Status::Failed;
+                entry.error = Some(e.to_string());
+            },
+        });
+    }
+
+    /// Change an entry and save the journal. A journal that can't be saved is reported
+    /// but doesn't stop the batch.
+    fn update(&self, index: usize, change: impl FnOnce(&mut Entry)) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(entry) = state.entries.get_mut(index) {
+            change(entry);
+        }
+        if let Err(e) = self.save(&state) {
+            eprintln!("Warning: {}", e);
+        }
+    }
+
+    /// Write the state to a temporary file first, so an interruption never leaves a
+    /// half-written journal behind
+    fn save(&self, state: &BatchState) -> Result<()> {
+        let json = serde_json::to_string_pretty(state)
+            .map_err(|e| Error::OutputParsing(e.to_string()))?;
+        let temp = self.path.with_extension("json.tmp");
+        std::fs::write(&temp, json)
+            .and_then(|_| std::fs::rename(&temp, &self.path))
+            .map_err(|e| Error::IoError(format!("Failed to save batch state {:?}: {}", self.path, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use crate::commands::{Cli, Commands};
+
+    #[test]
+    fn test_journal_resume() {
+        let cli = Cli::parse_from(["video-dl", "batch", "-u", "unused", "--select", "best"]);
+        let Commands::Batch { options, .. } = cli.command else {
+            panic!("expected the batch command");
+        };
+        let path = std::env::temp_dir().join(format!("video_dl_batch_{}.json", uuid::Uuid::new_v4()));
+        let urls = ["aaaaaaaaaaa", "bbbbbbbbbbb", "ccccccccccc", "ddddddddddd"]
+            .map(|id| format!("https://youtu.be/{}", id))
+            .to_vec();
+
+        let journal = Journal::create(&path, BatchState::new(urls, None, options, false)).unwrap();
+        journal.start(0);
+        journal.finish(0, &Ok(Downloaded::Archived { platform: "YouTube".into(), id: "aaaaaaaaaaa".into() }));
+        journal.start(1);
+        journal.finish(1, &Err(Error::NoSuitableFormats));
+        // Interrupted while downloading the third video
+        journal.start(2);
+
+        let resumed = Journal::load(&path).unwrap();
+        let remaining: Vec<_> = resumed.remaining(false).into_iter()
+            .map(|(index, entry)| (index, entry.status))
+            .collect();
+        assert_eq!(remaining, [(2, Status::Running), (3, Status::Pending)]);
+        assert_eq!(resumed.remaining(true).len(), 3);
+
+        let state = resumed.state();
+        assert_eq!(state.entries[0].status, Status::Done);
+        assert_eq!(state.entries[1].error.as_deref(), Some("No suitable formats found"));
+        assert_eq!(state.options.select.as_deref(), Some("best"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
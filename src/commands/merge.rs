@@ -1,20 +1,26 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs::File;
 use std::io::{BufRead, BufReader, Write};
 use tokio::process::Command;
 use indicatif::{ProgressBar, ProgressStyle};
 
 use crate::{Result, Error, Config};
+use crate::collision::{self, Decision};
 use crate::utils::dependency_check;
 
 /// Handles merging multiple videos into one
 pub async fn merge_command(
     files: Vec<PathBuf>,
     file_list: Option<PathBuf>,
-    output: PathBuf
+    output: PathBuf,
+    on_existing: Option<String>
 ) -> Result<()> {
     // Check if ffmpeg is available - required for merging
     let config = Config::load();
+    let on_existing = match on_existing {
+        Some(strategy) => strategy.parse()?,
+        None => config.existing_files(),
+    };
     let status = dependency_check::check_dependencies(&config).await;
     
     if !status.ffmpeg_available {
@@ -52,7 +58,16 @@ pub async fn merge_command(
         return Err(Error::InvalidArgument("At least two input files are required for merging".into()));
     }
     
-    println!("Merging {} videos into: {}", all_files.len(), output.display());
+    let (output, decision) = collision::resolve(output, on_existing, Path::to_path_buf)?;
+    if decision == Decision::Skipped {
+        println!("{} already exists, skipping merge", output.display());
+        return Ok(());
+    }
+    
+    match decision.note() {
+        Some(note) => println!("Merging {} videos into: {} ({})", all_files.len(), output.display(), note),
+        None => println!("Merging {} videos into: {}", all_files.len(), output.display()),
+    }
     
     // Create a temporary file for the concat list
     let temp_file = PathBuf::from(format!("concat_list_{}.txt", uuid::Uuid::new_v4()));
@@ -84,7 +99,8 @@ pub async fn merge_command(
     
     // Run ffmpeg to concatenate the videos
     let mut cmd = Command::new("ffmpeg");
-    cmd.args(["-f", "concat", "-safe", "0", "-i"])
+    // Existing outputs have been dealt with above, so ffmpeg may replace the file
+    cmd.args(["-y", "-f", "concat", "-safe", "0", "-i"])
        .arg(&temp_file)
        .args(["-c", "copy"]) // Copy streams without re-encoding
        .arg(&output);
@@ -4,16 +4,29 @@ mod config;
 mod batch;
 mod merge;
 mod download_merge;
+mod journal;
 
-pub use download::download_command;
-pub use info::info_command;
+pub use download::{download_command, download_info_json_command};
+pub use info::{info_command, info_json_command};
 pub use config::config_command;
-pub use batch::batch_download_command;
+pub use batch::{batch_download_command, resume_batch_command};
 pub use merge::merge_command;
 pub use download_merge::download_merge_command; 
 
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+
+use crate::{Config, FormatSelector, PlaylistRange, PostProcessor, Result, Section, VideoInfo};
+use crate::postprocess::audio::AudioOptions;
+use crate::postprocess::subtitles::SubtitleOptions;
+use crate::postprocess::thumbnail::ThumbnailOptions;
+use crate::archive::Archive;
+use crate::info_json::ToolVersions;
+use crate::collision::OnExisting;
+use crate::template::OutputTemplate;
 
 #[derive(Parser)]
 #[command(name = "video-dl")]
@@ -28,18 +41,28 @@ pub struct Cli {
 #[derive(Subcommand)]
 pub enum Commands {
     Download {
-        #[arg(short, long)]
-        url: String,
-        #[arg(short, long)]
+        #[arg(short, long, required_unless_present = "load_info_json")]
+        url: Option<String>,
+        #[arg(long, conflicts_with = "url", help = "Download from a .info.json file instead of extracting the URL again")]
+        load_info_json: Option<PathBuf>,
+        #[arg(short, long, help = "Output file, or output directory for playlists")]
         output: Option<PathBuf>,
-        #[arg(short, long, default_value = "best")]
-        quality: String,
-        #[arg(short, long, default_value = "mp4")]
-        format: String,
+        #[command(flatten)]
+        options: DownloadOptions,
     },
     Info {
+        #[arg(short, long, required_unless_present = "load_info_json")]
+        url: Option<String>,
+        #[arg(long, conflicts_with = "url", help = "Show a .info.json file instead of extracting the URL")]
+        load_info_json: Option<PathBuf>,
+    },
+    Audio {
         #[arg(short, long)]
         url: String,
+        #[arg(short, long, help = "Output file, or output directory for playlists")]
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        options: DownloadOptions,
     },
     Config {
         #[command(subcommand)]
@@ -55,14 +78,20 @@ pub enum Commands {
         #[arg(short = 'd', long, help = "Output directory")]
         output_dir: Option<PathBuf>,
         
-        #[arg(short = 'q', long, default_value = "best", help = "Video quality")]
-        quality: String,
-        
-        #[arg(short = 'f', long, default_value = "mp4", help = "Video format")]
-        format: String,
+        #[command(flatten)]
+        options: DownloadOptions,
         
         #[arg(short = 'p', long, help = "Download videos in parallel")]
         parallel: bool,
+        
+        #[arg(long, value_name = "PATH", help = "Batch state file [default: video-dl-batch-<time>.json in the output directory]")]
+        state: Option<PathBuf>,
+        
+        #[arg(long, value_name = "STATE", conflicts_with_all = ["url", "file", "state"], help = "Continue an interrupted batch from its state file, with the options it was started with")]
+        resume: Option<PathBuf>,
+        
+        #[arg(long, requires = "resume", help = "With --resume, also retry downloads that failed")]
+        retry_failed: bool,
     },
     Merge {
         #[arg(short, long, help = "Video files to merge (can specify multiple)")]
@@ -73,6 +102,9 @@ pub enum Commands {
         
         #[arg(short, long, required = true, help = "Output file path")]
         output: PathBuf,
+        
+        #[arg(long, value_parser = ["skip", "overwrite", "rename", "fail"], help = "What to do when the output file already exists [default: from config]")]
+        on_existing: Option<String>,
     },
     // Thêm lệnh mới DownloadMerge
     DownloadMerge {
@@ -85,17 +117,271 @@ pub enum Commands {
         #[arg(short, long, required = true, help = "Output file path")]
         output: PathBuf,
         
-        #[arg(short = 'q', long, default_value = "best", help = "Video quality")]
-        quality: String,
-        
-        #[arg(short = 'f', long, default_value = "mp4", help = "Video format")]
-        format: String,
+        #[command(flatten)]
+        options: DownloadOptions,
         
         #[arg(short = 'p', long, help = "Download videos in parallel")]
         parallel: bool,
     },
 }
 
+/// Options shared by every command that downloads videos
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadOptions {
+    #[arg(short = 'q', long, default_value = "best", help = "Video quality")]
+    pub quality: String,
+    
+    #[arg(short = 'f', long, default_value = "mp4", help = "Video format")]
+    pub format: String,
+    
+    #[arg(long, help = "Format selector, e.g. \"bestvideo[height<=1080]+bestaudio/best\"; overrides --quality and --format")]
+    pub select: Option<String>,
+    
+    #[arg(long, help = "First playlist entry to download (1-based)")]
+    pub playlist_start: Option<usize>,
+    
+    #[arg(long, help = "Last playlist entry to download (inclusive)")]
+    pub playlist_end: Option<usize>,
+    
+    #[arg(long, help = "Maximum number of playlist entries to download")]
+    pub max_items: Option<usize>,
+    
+    #[arg(long, value_parser = ["hour", "day", "week", "month", "year", "all"], help = "Time window for top-ranked listings, e.g. Reddit top of the week")]
+    pub time: Option<String>,
+    
+    #[arg(short = 'x', long, help = "Download only the audio track and convert it with ffmpeg")]
+    pub extract_audio: bool,
+    
+    #[arg(long, default_value = "mp3", value_parser = ["mp3", "m4a", "opus", "flac"], help = "Audio format for --extract-audio")]
+    pub audio_format: String,
+    
+    #[arg(long, help = "Audio bitrate for --extract-audio, e.g. 192k (ignored for flac)")]
+    pub audio_quality: Option<String>,
+    
+    #[arg(long, help = "Save subtitles next to the video")]
+    pub write_subs: bool,
+    
+    #[arg(long, help = "Embed subtitles into the video as soft subtitle streams")]
+    pub embed_subs: bool,
+    
+    #[arg(long, help = "Use automatically generated captions for languages without subtitles")]
+    pub auto_subs: bool,
+    
+    #[arg(long, default_value = "en", help = "Subtitle languages, comma-separated, or \"all\"")]
+    pub sub_langs: String,
+    
+    #[arg(long, default_value = "srt", value_parser = ["srt", "vtt"], help = "Subtitle file format")]
+    pub sub_format: String,
+    
+    #[arg(long, help = "Write title, uploader, date, description, URL and chapters into the file's metadata")]
+    pub embed_metadata: bool,
+    
+    #[arg(long, help = "Output file name template, e.g. \"{uploader}/{title} [{id}].{ext}\"; fields: title, id, uploader, upload_date, platform, height, ext, index")]
+    pub output_template: Option<String>,
+    
+    #[arg(long, value_parser = ["skip", "overwrite", "rename", "fail"], help = "What to do when an output file already exists [default: from config]")]
+    pub on_existing: Option<String>,
+    
+    #[arg(long, value_name = "PATH", help = "Download archive: skip videos listed in this file and add new downloads to it")]
+    pub archive: Option<PathBuf>,
+    
+    #[arg(long, help = "Download videos even if they are in the download archive")]
+    pub force: bool,
+    
+    #[arg(long, value_name = "START-END", help = "Download only a time range, e.g. 1:30-2:00 or 90-120")]
+    pub section: Option<String>,
+    
+    #[arg(long, help = "Re-encode so sections start exactly at START instead of the nearest keyframe")]
+    pub accurate_cuts: bool,
+    
+    #[arg(long, conflicts_with = "section", help = "Download only the part given by the URL's start time or clip")]
+    pub url_section: bool,
+    
+    #[arg(long, help = "Also save each chapter of the video as a separate file")]
+    pub split_chapters: bool,
+    
+    #[arg(long, help = "Write the extracted video information to a .info.json file next to the download")]
+    pub write_info_json: bool,
+    
+    #[arg(long, help = "Save the video's thumbnail next to the downloaded file")]
+    pub write_thumbnail: bool,
+    
+    #[arg(long, help = "Embed the thumbnail into the file as cover art")]
+    pub embed_thumbnail: bool,
+}
+
+/// Which part of each video to download
+#[derive(Debug, Clone)]
+pub enum SectionChoice {
+    /// The time range in the video's URL, such as a start time or a clip, if it has one
+    FromUrl { accurate: bool },
+    /// The whole video, ignoring any time range in the URL
+    Whole,
+    /// The same range of every video
+    Range(Section),
+}
+
+impl SectionChoice {
+    /// The section to download from an extracted video, or `None` for all of it
+    pub fn resolve(&self, info: &VideoInfo) -> Option<Section> {
+        match self {
+            SectionChoice::FromUrl { accurate } => info.section.clone().map(|section| Section { accurate: *accurate, ..section }),
+            SectionChoice::Whole => None,
+            SectionChoice::Range(section) => Some(section.clone()),
+        }
+    }
+}
+
+/// How each video of a command is downloaded
+#[derive(Debug, Clone)]
+pub struct DownloadSettings {
+    pub selector: FormatSelector,
+    pub post: PostProcessor,
+    pub section: SectionChoice,
+    /// What to do with outputs that already exist
+    pub on_existing: OnExisting,
+    /// Videos downloaded before, and where new downloads are recorded
+    pub archive: Option<Archive>,
+    /// Download videos even if the archive lists them
+    pub force: bool,
+    /// Tool versions for `.info.json` files, looked up on first use and shared by clones
+    pub tool_versions: Arc<OnceCell<ToolVersions>>,
+}
+
+impl DownloadSettings {
+    /// Whether the archive lists a video, unless `--force` is given
+    pub fn in_archive(&self, platform: &str, id: &str) -> bool {
+        !self.force && self.archive.as_ref().is_some_and(|archive| archive.contains(platform, id))
+    }
+
+    /// Settings for retrying an interrupted download. The file it left behind may be
+    /// truncated, so it is replaced rather than kept or reported; with `Rename` the
+    /// existing file may belong to another video, so a new name is still picked.
+    pub fn for_interrupted(&self) -> Self {
+        let on_existing = match self.on_existing {
+            OnExisting::Rename => OnExisting::Rename,
+            _ => OnExisting::Overwrite,
+        };
+        Self { on_existing, ..self.clone() }
+    }
+}
+
+impl DownloadOptions {
+    /// The saved configuration with the overrides given on the command line
+    pub fn load_config(&self) -> Result<Config> {
+        let mut config = Config::load();
+        if let Some(template) = &self.output_template {
+            config.output_template = template.clone();
+        }
+        // Platforms that pick a rendition themselves read the quality from the config
+        if self.quality != "best" {
+            config.default_quality = self.quality.clone();
+        }
+        if let Some(archive) = &self.archive {
+            config.archive = Some(archive.clone());
+        }
+        if let Some(on_existing) = &self.on_existing {
+            config.on_existing = Some(on_existing.parse()?);
+        }
+        OutputTemplate::parse(&config.output_template)?;
+        Ok(config)
+    }
+
+    /// Format, post-processing and section settings for every video of a command
+    pub fn settings(&self, config: &Config) -> Result<DownloadSettings> {
+        Ok(DownloadSettings {
+            selector: self.format_selector(config)?,
+            post: self.post_processor()?,
+            section: self.section()?,
+            on_existing: config.existing_files(),
+            archive: config.archive.as_deref().map(Archive::load).transpose()?,
+            force: self.force,
+            tool_versions: Arc::default(),
+        })
+    }
+
+    /// Selector for the formats to download: `--select` if given, otherwise built from
+    /// `--quality` and `--format`, falling back to the configured defaults
+    pub fn format_selector(&self, config: &Config) -> Result<FormatSelector> {
+        if let Some(expression) = &self.select {
+            return FormatSelector::parse(expression);
+        }
+
+        // Prefer a separate audio stream, otherwise take the audio from a muxed format
+        if self.extract_audio {
+            return FormatSelector::parse("ba/ba*");
+        }
+
+        let quality = if self.quality == "best" { &config.default_quality } else { &self.quality };
+        let format = if self.format == "mp4" { &config.default_format } else { &self.format };
+        FormatSelector::from_preferences(quality, format)
+    }
+
+    /// Steps to run on each file after it has been downloaded
+    pub fn post_processor(&self) -> Result<PostProcessor> {
+        let extract_audio = if self.extract_audio {
+            Some(AudioOptions::new(self.audio_format.parse()?, self.audio_quality.clone())?)
+        } else {
+            None
+        };
+
+        let subtitles = if self.write_subs || self.embed_subs {
+            Some(SubtitleOptions {
+                languages: self.sub_langs.split(',')
+                    .map(|l| l.trim().to_string())
+                    .filter(|l| !l.is_empty())
+                    .collect(),
+                include_auto: self.auto_subs,
+                format: self.sub_format.parse()?,
+                write: self.write_subs,
+                embed: self.embed_subs,
+            })
+        } else {
+            None
+        };
+
+        let thumbnail = if self.write_thumbnail || self.embed_thumbnail {
+            Some(ThumbnailOptions {
+                write: self.write_thumbnail,
+                embed: self.embed_thumbnail,
+            })
+        } else {
+            None
+        };
+
+        Ok(PostProcessor {
+            extract_audio,
+            subtitles,
+            embed_metadata: self.embed_metadata,
+            thumbnail,
+            split_chapters: self.split_chapters,
+            write_info_json: self.write_info_json,
+        })
+    }
+
+    /// Part of each video to download: `--section`, the time range in the URL with
+    /// `--url-section`, otherwise the whole video
+    pub fn section(&self) -> Result<SectionChoice> {
+        if let Some(value) = &self.section {
+            return Ok(SectionChoice::Range(Section { accurate: self.accurate_cuts, ..value.parse()? }));
+        }
+        if self.url_section {
+            return Ok(SectionChoice::FromUrl { accurate: self.accurate_cuts });
+        }
+        Ok(SectionChoice::Whole)
+    }
+
+    /// Range of entries to take from playlist, channel and profile URLs
+    pub fn playlist_range(&self) -> PlaylistRange {
+        PlaylistRange {
+            start: self.playlist_start,
+            end: self.playlist_end,
+            max_items: self.max_items,
+            time: self.time.clone(),
+        }
+    }
+}
+
 #[derive(Subcommand)]
 pub enum ConfigAction {
     Set {
@@ -2,7 +2,10 @@ use std::path::PathBuf;
 use std::fs;
 use serde::{Deserialize, Serialize};
 
+use crate::collision::OnExisting;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     /// Path to yt-dlp executable, if not in PATH
     pub ytdlp_path: Option<PathBuf>,
@@ -19,8 +22,20 @@ pub struct Config {
     /// Whether to show progress bars
     pub show_progress: bool,
     
-    /// Whether to overwrite existing files
+    /// Whether to overwrite existing files; `on_existing` takes precedence when set
     pub overwrite_files: bool,
+    
+    /// What to do when an output file already exists
+    pub on_existing: Option<OnExisting>,
+    
+    /// Number of parallel connections used for direct HTTP downloads
+    pub connections: usize,
+    
+    /// Output file name template, relative to the download directory
+    pub output_template: String,
+    
+    /// Download archive file listing videos to skip, if any
+    pub archive: Option<PathBuf>,
 }
 
 impl Config {
@@ -60,6 +75,16 @@ impl Config {
         config
     }
     
+    /// Strategy for existing output files: `on_existing`, or else overwrite or skip
+    /// according to `overwrite_files`
+    pub fn existing_files(&self) -> OnExisting {
+        match self.on_existing {
+            Some(strategy) => strategy,
+            None if self.overwrite_files => OnExisting::Overwrite,
+            None => OnExisting::Skip,
+        }
+    }
+    
     /// Save config to file
     pub fn save(&self) -> Result<(), std::io::Error> {
         let config_dir = dirs::config_dir()
@@ -72,7 +97,7 @@ impl Config {
         
         let config_path = config_dir.join("config.toml");
         let content = toml::to_string_pretty(self)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+            .map_err(std::io::Error::other)?;
             
         fs::write(config_path, content)
     }
@@ -87,6 +112,10 @@ impl Default for Config {
             default_format: "mp4".to_string(),
             show_progress: true,
             overwrite_files: false,
+            on_existing: None,
+            connections: 4,
+            output_template: "{title}.{ext}".to_string(),
+            archive: None,
         }
     }
 }
\ No newline at end of file
@@ -1,7 +1,12 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use futures_util::TryStreamExt;
+use tokio::sync::watch;
 
-use crate::{Result, platform::normalize_url, Config};
+use crate::{Result, platform::normalize_url, Config, Section, VideoInfo};
+use crate::platform::{Platform, PlaylistEntry, PlaylistRange};
+use crate::template::{OutputTemplate, TemplateContext};
+use crate::utils::ffmpeg;
 use crate::platform::detector::PlatformDetector;
 use crate::utils::progress::ProgressTracker;
 
@@ -13,16 +18,13 @@ pub struct Downloader {
 
 impl Downloader {
     pub fn new() -> Self {
-        Self {
-            detector: PlatformDetector::new(),
-            config: Config::load(),
-        }
+        Self::with_config(Config::load())
     }
     
     /// Create a new downloader with a custom configuration
     pub fn with_config(config: Config) -> Self {
         Self {
-            detector: PlatformDetector::new(),
+            detector: PlatformDetector::with_config(&config),
             config,
         }
     }
@@ -37,28 +39,72 @@ impl Downloader {
         &mut self.config
     }
 
-    pub async fn get_video_info(&self, url: &str) -> Result<crate::platform::VideoInfo> {
+    pub async fn get_video_info(&self, url: &str) -> Result<VideoInfo> {
         let url = normalize_url(url)?;
         let platform = self.detector.detect(&url)?;
         platform.extract_info(&url).await
     }
 
-    pub async fn download(&self, url: &str, format_id: &str, output: Option<PathBuf>) -> Result<PathBuf> {
+    /// Check whether a URL points to a playlist, channel, profile or listing
+    pub fn is_collection(&self, url: &str) -> Result<bool> {
+        let url = normalize_url(url)?;
+        let platform = self.detector.detect(&url)?;
+        Ok(platform.is_collection(&url))
+    }
+
+    /// Platform name and video ID of a URL, if the ID can be read without extracting the video
+    pub fn video_id(&self, url: &str) -> Result<Option<(&'static str, String)>> {
         let url = normalize_url(url)?;
         let platform = self.detector.detect(&url)?;
-        let info = platform.extract_info(&url).await?;
+        Ok(platform.video_id(&url).map(|id| (platform.name(), id)))
+    }
+
+    /// Expand a URL into the videos it refers to.
+    ///
+    /// Collection URLs are expanded page by page until `range` is satisfied; any other
+    /// URL yields a single entry for itself.
+    pub async fn expand(&self, url: &str, range: &PlaylistRange) -> Result<Vec<PlaylistEntry>> {
+        let url = normalize_url(url)?;
+        let platform = self.detector.detect(&url)?;
+
+        if !platform.is_collection(&url) {
+            return Ok(vec![PlaylistEntry { url, title: None }]);
+        }
+
+        platform.expand(&url, range).try_collect().await
+    }
+
+    pub async fn download(&self, url: &str, format_id: &str, output: Option<PathBuf>) -> Result<PathBuf> {
+        let info = self.get_video_info(url).await?;
+        self.download_info(&info, format_id, output, None).await
+    }
+
+    /// Path for a download of `info` from the configured output template, inside `dir` or
+    /// the download directory. Directories in the path are created.
+    pub fn output_path(&self, info: &VideoInfo, context: &TemplateContext<'_>, dir: Option<&Path>) -> Result<PathBuf> {
+        let template = OutputTemplate::parse(&self.config.output_template)?;
+        let path = dir.unwrap_or(&self.config.download_dir).join(template.render(info, context));
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Ok(path)
+    }
 
-        // Use configured output directory if none is specified
+    /// Download a format of an already extracted video without extracting it again,
+    /// optionally only a time range of it
+    pub async fn download_info(
+        &self,
+        info: &VideoInfo,
+        format_id: &str,
+        output: Option<PathBuf>,
+        section: Option<&Section>
+    ) -> Result<PathBuf> {
+        let platform = self.detector.detect(&info.url)?;
+
+        // Name the file after the output template in the download directory if no path is given
         let output_path = match output {
             Some(path) => path,
-            None => {
-                // Use configuration download directory
-                let filename = format!(
-                    "{}.mp4",
-                    sanitize_filename::sanitize(&info.title),
-                );
-                self.config.download_dir.join(filename)
-            }
+            None => self.output_path(info, &TemplateContext { format_id, index: None }, None)?,
         };
         
         // Create progress tracker if configured to show progress
@@ -67,21 +113,16 @@ impl Downloader {
         } else {
             None
         };
+        let progress_tx = match &progress {
+            Some(tracker) => tracker.get_sender(),
+            // Create a dummy progress sender
+            None => Arc::new(tokio::sync::watch::channel(0.0).0),
+        };
         
         // Download the video
-        let result = match &progress {
-            Some(tracker) => {
-                platform
-                    .download_video(&info, format_id, &output_path, tracker.get_sender())
-                    .await
-            }
-            None => {
-                // Create a dummy progress sender
-                let (tx, _) = tokio::sync::watch::channel(0.0);
-                platform
-                    .download_video(&info, format_id, &output_path, Arc::new(tx))
-                    .await
-            }
+        let result = match section {
+            None => platform.download_video(info, format_id, &output_path, progress_tx).await,
+            Some(section) => download_section(platform.as_ref(), info, format_id, section, &output_path, progress_tx).await,
         };
         
         // Finish the progress tracker if used
@@ -94,6 +135,29 @@ impl Downloader {
     }
 }
 
+/// Download a section through the platform, or cut it out of a full download when the
+/// platform can only fetch whole videos
+async fn download_section(
+    platform: &dyn Platform,
+    info: &VideoInfo,
+    format_id: &str,
+    section: &Section,
+    output_path: &Path,
+    progress_tx: Arc<watch::Sender<f64>>
+) -> Result<()> {
+    if platform.download_section(info, format_id, section, output_path, progress_tx.clone()).await? {
+        return Ok(());
+    }
+
+    let extension = output_path.extension().and_then(|e| e.to_str()).unwrap_or("mp4");
+    let full_path = output_path.with_extension(format!("full.{}", extension));
+
+    platform.download_video(info, format_id, &full_path, progress_tx).await?;
+    let result = ffmpeg::cut(&full_path, output_path, section).await;
+    let _ = tokio::fs::remove_file(&full_path).await;
+    result
+}
+
 impl Default for Downloader {
     fn default() -> Self {
         Self::new()
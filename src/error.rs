@@ -38,6 +38,9 @@ pub enum Error {
     #[error("Invalid output path: {0}")]
     InvalidOutputPath(PathBuf),
     
+    #[error("Output file already exists: {0}")]
+    FileExists(PathBuf),
+    
     #[error("No suitable formats found")]
     NoSuitableFormats,
 
@@ -0,0 +1,145 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::dependency_check;
+use crate::{Config, Error, Result, VideoInfo};
+
+/// Machine-readable record of a download, written as `<file stem>.info.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InfoJson {
+    #[serde(flatten)]
+    pub info: VideoInfo,
+    /// Format ID that was downloaded, with `+` joining separate video and audio streams
+    pub format_id: String,
+    /// URL as given on the command line, before any redirects or canonicalization
+    pub source_url: String,
+    /// Unix timestamp of the extraction
+    pub extracted_at: u64,
+    pub versions: ToolVersions,
+}
+
+/// Versions of the programs involved in a download
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolVersions {
+    pub video_dl: String,
+    pub yt_dlp: Option<String>,
+    pub ffmpeg: Option<String>,
+}
+
+impl ToolVersions {
+    /// This program's version and those of the installed yt-dlp and ffmpeg
+    pub async fn detect(config: &Config) -> Self {
+        let status = dependency_check::check_dependencies(config).await;
+        Self {
+            video_dl: env!("CARGO_PKG_VERSION").to_string(),
+            yt_dlp: status.yt_dlp_version,
+            ffmpeg: status.ffmpeg_version,
+        }
+    }
+}
+
+impl InfoJson {
+    /// Record `info` as extracted now
+    pub fn new(
+        info: &VideoInfo,
+        format_id: &str,
+        source_url: &str,
+        versions: &ToolVersions
+    ) -> Self {
+        let extracted_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        Self {
+            info: info.clone(),
+            format_id: format_id.to_string(),
+            source_url: source_url.to_string(),
+            extracted_at,
+            versions: versions.clone(),
+        }
+    }
+
+    /// Read a record written by [`InfoJson::write`]
+    pub async fn load(path: &Path) -> Result<Self> {
+        let text = tokio::fs::read_to_string(path).await
+            .map_err(|e| Error::IoError(format!("Failed to read {:?}: {}", path, e)))?;
+        serde_json::from_str(&text)
+            .map_err(|e| Error::OutputParsing(format!("Invalid info JSON {:?}: {}", path, e)))
+    }
+
+    /// Write the record next to `file`, returning the path of the JSON file
+    pub async fn write(&self, file: &Path) -> Result<PathBuf> {
+        let path = sidecar_path(file);
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::OutputParsing(e.to_string()))?;
+        tokio::fs::write(&path, json).await?;
+        Ok(path)
+    }
+}
+
+fn sidecar_path(file: &Path) -> PathBuf {
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    file.with_file_name(format!("{}.info.json", stem))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use url::Url;
+    use crate::{Format, Quality, VideoFormat};
+
+    #[test]
+    fn test_info_json_round_trip() {
+        let record = InfoJson {
+            info: VideoInfo {
+                url: Url::parse("https://www.youtube.com/watch?v=dQw4w9WgXcQ").unwrap(),
+                id: Some("dQw4w9WgXcQ".to_string()),
+                platform: "YouTube".to_string(),
+                webpage_url: None,
+                title: "Title".to_string(),
+                description: None,
+                duration: Some(212),
+                formats: vec![VideoFormat {
+                    height: Some(1080),
+                    ..VideoFormat::new("137", Quality::HD1080, Format::MP4)
+                }],
+                subtitles: Vec::new(),
+                thumbnails: Vec::new(),
+                uploader: Some("Rick Astley".to_string()),
+                uploader_id: Some("@RickAstleyYT".to_string()),
+                upload_date: Some("20091025".to_string()),
+                view_count: Some(1_600_000_000),
+                like_count: None,
+                comment_count: None,
+                tags: vec!["rick astley".to_string()],
+                categories: vec!["Music".to_string()],
+                chapters: Vec::new(),
+                section: None,
+            },
+            format_id: "137+140".to_string(),
+            source_url: "https://youtu.be/dQw4w9WgXcQ".to_string(),
+            extracted_at: 1_700_000_000,
+            versions: ToolVersions { video_dl: "0.3.0".to_string(), yt_dlp: None, ffmpeg: None },
+        };
+
+        let json = serde_json::to_value(&record).unwrap();
+        assert_eq!(json["title"], "Title");
+        assert_eq!(json["format_id"], "137+140");
+        assert_eq!(json["platform"], "YouTube");
+
+        let loaded: InfoJson = serde_json::from_value(json).unwrap();
+        assert_eq!(loaded.info.url, record.info.url);
+        assert_eq!(loaded.info.formats[0].height, Some(1080));
+        assert_eq!(loaded.info.categories, ["Music"]);
+        assert_eq!(sidecar_path(Path::new("out/Title.mp4")), Path::new("out/Title.info.json"));
+
+        // Records from other tools may leave out anything but the URL and title
+        let minimal: VideoInfo = serde_json::from_value(serde_json::json!({
+            "url": "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "title": "Title",
+        })).unwrap();
+        assert!(minimal.subtitles.is_empty() && minimal.thumbnails.is_empty() && minimal.chapters.is_empty());
+    }
+}
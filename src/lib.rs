@@ -1,12 +1,21 @@
 mod error;
 mod platform;
 pub mod downloader;
+pub mod selector;
+pub mod postprocess;
+pub mod info_json;
+pub mod template;
+pub mod collision;
+pub mod archive;
 pub mod commands;
 pub mod config;
 pub mod utils;
 
 pub use error::{Error, Result};
-pub use platform::{Platform, Quality, Format, VideoFormat, VideoInfo};
+pub use platform::{Platform, PlaylistEntry, PlaylistRange, Section, Quality, Format, SubtitleTrack, Thumbnail, Chapter, VideoFormat, VideoInfo};
 pub use platform::detector::PlatformDetector;
 pub use downloader::Downloader;
+pub use selector::FormatSelector;
+pub use postprocess::PostProcessor;
+pub use info_json::InfoJson;
 pub use config::Config;
\ No newline at end of file
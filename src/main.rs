@@ -4,7 +4,8 @@ use video_dl::utils::dependency_check;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    env_logger::init();
+    // Warnings are shown unless RUST_LOG says otherwise
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
     let cli = Cli::parse();
 
     // Load configuration
@@ -22,7 +23,12 @@ async fn main() -> Result<()> {
     }
 
     match cli.command {
-        Commands::Download { url, output, quality, format } => {
+        Commands::Download { url, load_info_json, output, options } => {
+            // Audio extraction always converts with ffmpeg
+            if options.extract_audio {
+                dependency_check::require_ffmpeg(&status, "extracting audio")?;
+            }
+            
             // For download, we ideally want both yt-dlp and ffmpeg
             if !dependency_check::all_dependencies_available(&status) {
                 eprintln!("Warning: Some dependencies are missing. Limited functionality available.");
@@ -30,15 +36,30 @@ async fn main() -> Result<()> {
                 eprintln!("Continuing anyway...");
             }
             
-            video_dl::commands::download_command(url, output, quality, format).await
+            match (url, load_info_json) {
+                (_, Some(path)) => video_dl::commands::download_info_json_command(path, output, options).await,
+                (Some(url), None) => video_dl::commands::download_command(url, output, options).await,
+                (None, None) => unreachable!("clap requires --url or --load-info-json"),
+            }
+        }
+        Commands::Audio { url, output, mut options } => {
+            // Audio extraction always converts with ffmpeg
+            dependency_check::require_ffmpeg(&status, "extracting audio")?;
+            
+            options.extract_audio = true;
+            video_dl::commands::download_command(url, output, options).await
         }
-        Commands::Info { url } => {
-            video_dl::commands::info_command(url).await
+        Commands::Info { url, load_info_json } => {
+            match (url, load_info_json) {
+                (_, Some(path)) => video_dl::commands::info_json_command(path).await,
+                (Some(url), None) => video_dl::commands::info_command(url).await,
+                (None, None) => unreachable!("clap requires --url or --load-info-json"),
+            }
         }
         Commands::Config { action } => {
             video_dl::commands::config_command(action).await
         }
-        Commands::Batch { url, file, output_dir, quality, format, parallel } => {
+        Commands::Batch { url, file, output_dir, options, parallel, state, resume, retry_failed } => {
             // For batch download, we ideally want both yt-dlp and ffmpeg
             if !dependency_check::all_dependencies_available(&status) {
                 eprintln!("Warning: Some dependencies are missing. Limited functionality available.");
@@ -46,31 +67,22 @@ async fn main() -> Result<()> {
                 eprintln!("Continuing anyway...");
             }
             
-            video_dl::commands::batch_download_command(url, file, output_dir, quality, format, parallel).await
+            match resume {
+                Some(state) => video_dl::commands::resume_batch_command(state, retry_failed).await,
+                None => video_dl::commands::batch_download_command(url, file, output_dir, options, parallel, state).await,
+            }
         }
-        Commands::Merge { files, file_list, output } => {
+        Commands::Merge { files, file_list, output, on_existing } => {
             // For merge, we need ffmpeg
-            if !status.ffmpeg_available {
-                dependency_check::print_dependency_status(&status);
-                return Err(video_dl::Error::CommandExecution { 
-                    command: "ffmpeg".to_string(), 
-                    reason: "ffmpeg is required for merging videos".to_string() 
-                });
-            }
+            dependency_check::require_ffmpeg(&status, "merging videos")?;
             
-            video_dl::commands::merge_command(files, file_list, output).await
+            video_dl::commands::merge_command(files, file_list, output, on_existing).await
         }
-        Commands::DownloadMerge { url, file, output, quality, format, parallel } => {
+        Commands::DownloadMerge { url, file, output, options, parallel } => {
             // For download-merge, we need both yt-dlp and ffmpeg
-            if !status.ffmpeg_available {
-                dependency_check::print_dependency_status(&status);
-                return Err(video_dl::Error::CommandExecution { 
-                    command: "ffmpeg".to_string(), 
-                    reason: "ffmpeg is required for merging videos".to_string() 
-                });
-            }
+            dependency_check::require_ffmpeg(&status, "merging videos")?;
             
-            video_dl::commands::download_merge_command(url, file, output, quality, format, parallel).await
+            video_dl::commands::download_merge_command(url, file, output, options, parallel).await
         }
     }
 }
@@ -0,0 +1,641 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use bytes::Bytes;
+use futures_util::stream::{self, StreamExt, TryStreamExt};
+use log::{debug, warn};
+use reqwest::{header, Client};
+use roxmltree::{Document, Node};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::watch;
+use url::Url;
+
+use super::{split_codecs, Format, Quality, VideoFormat};
+use crate::utils::ffmpeg;
+use crate::{Error, Result};
+
+/// Upper bound on segments generated from a template, to guard against malformed manifests
+const MAX_TEMPLATE_SEGMENTS: u64 = 100_000;
+
+/// Kind of media carried by a representation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamKind {
+    Video,
+    Audio,
+    Other,
+}
+
+/// A fetchable piece of a representation, optionally restricted to an inclusive byte range
+#[derive(Debug, Clone, PartialEq)]
+pub struct SegmentRef {
+    pub url: Url,
+    pub byte_range: Option<(u64, u64)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Representation {
+    pub id: String,
+    pub kind: StreamKind,
+    pub mime_type: String,
+    pub codecs: Option<String>,
+    pub bandwidth: u64,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub frame_rate: Option<f64>,
+    pub language: Option<String>,
+    pub init: Option<SegmentRef>,
+    pub segments: Vec<SegmentRef>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    pub duration: Option<f64>,
+    pub representations: Vec<Representation>,
+}
+
+impl Manifest {
+    pub fn representation(&self, id: &str) -> Option<&Representation> {
+        self.representations.iter().find(|r| r.id == id)
+    }
+
+    /// Best video representation within the quality's height limit, plus the best audio one
+    pub fn select(&self, quality: Option<&Quality>) -> Vec<&Representation> {
+        let max_height = quality.and_then(|q| q.max_height());
+        let videos = || self.representations.iter().filter(|r| r.kind == StreamKind::Video);
+        let by_quality = |a: &&Representation, b: &&Representation| {
+            (a.height, a.bandwidth).cmp(&(b.height, b.bandwidth))
+        };
+
+        let video = match max_height {
+            Some(limit) => videos()
+                .filter(|r| r.height.is_none_or(|h| h <= limit))
+                .max_by(by_quality)
+                .or_else(|| videos().min_by(by_quality)),
+            None => videos().max_by(by_quality),
+        };
+
+        video.into_iter().chain(self.best_audio()).collect()
+    }
+
+    /// Resolve a format ID into representations to download.
+    ///
+    /// `format_id` is `best` or one or more `dash-<id>` formats joined with `+`. A selection
+    /// without audio is paired with the best audio representation.
+    pub fn select_formats(&self, format_id: &str) -> Result<Vec<&Representation>> {
+        let mut selected = if format_id == "best" {
+            self.select(None)
+        } else {
+            format_id.split('+')
+                .map(|id| {
+                    id.strip_prefix("dash-")
+                        .and_then(|rep_id| self.representation(rep_id))
+                        .ok_or_else(|| Error::InvalidFormat(id.to_string()))
+                })
+                .collect::<Result<Vec<_>>>()?
+        };
+
+        if !selected.iter().any(|r| r.kind == StreamKind::Audio) {
+            selected.extend(self.best_audio());
+        }
+
+        Ok(selected)
+    }
+
+    /// Highest-bandwidth audio representation
+    pub fn best_audio(&self) -> Option<&Representation> {
+        self.representations.iter()
+            .filter(|r| r.kind == StreamKind::Audio)
+            .max_by_key(|r| r.bandwidth)
+    }
+}
+
+/// Format ID used to refer to a representation
+pub fn format_id(representation: &Representation) -> String {
+    format!("dash-{}", representation.id)
+}
+
+/// Describe a representation as a `VideoFormat`
+pub fn to_video_format(representation: &Representation, duration: Option<f64>) -> VideoFormat {
+    let format = match (representation.kind, representation.mime_type.as_str()) {
+        (StreamKind::Audio, "audio/mp4") => Format::Other("m4a".to_string()),
+        (_, mime) if mime.ends_with("/webm") => Format::WebM,
+        (_, mime) if mime.ends_with("/mp4") => Format::MP4,
+        (_, mime) => Format::Other(mime.rsplit('/').next().unwrap_or(mime).to_string()),
+    };
+
+    let quality = match representation.kind {
+        StreamKind::Audio => Quality::Custom(format!("audio {}k", representation.bandwidth / 1000)),
+        _ => representation.height.map(Quality::from_height).unwrap_or(Quality::Medium),
+    };
+
+    let (vcodec, acodec) = representation.codecs.as_deref().map(split_codecs).unwrap_or((None, None));
+    let has_video = representation.kind == StreamKind::Video;
+    let has_audio = representation.kind == StreamKind::Audio || acodec.is_some();
+    let kbps = (representation.bandwidth / 1000) as u32;
+
+    VideoFormat {
+        file_size: duration.map(|d| (representation.bandwidth as f64 / 8.0 * d) as u64),
+        width: representation.width,
+        height: representation.height,
+        fps: representation.frame_rate,
+        hdr: vcodec.as_deref().is_some_and(|codec| codec.starts_with("dvh")),
+        vcodec: vcodec.filter(|_| has_video),
+        acodec: acodec.filter(|_| has_audio),
+        video_bitrate: Some(kbps).filter(|_| has_video),
+        audio_bitrate: Some(kbps).filter(|_| !has_video),
+        language: representation.language.clone(),
+        has_video,
+        has_audio,
+        ..VideoFormat::new(format_id(representation), quality, format)
+    }
+}
+
+/// Parse an ISO 8601 duration such as `PT1H2M3.5S` into seconds
+pub fn parse_duration(value: &str) -> Option<f64> {
+    let rest = value.trim().strip_prefix('P')?;
+    let (date, time) = rest.split_once('T').unwrap_or((rest, ""));
+    let mut seconds = 0.0;
+
+    let mut consume = |part: &str, units: &[(char, f64)]| -> Option<()> {
+        let mut number = String::new();
+        for c in part.chars() {
+            if c.is_ascii_digit() || c == '.' {
+                number.push(c);
+            } else {
+                let factor = units.iter().find(|(u, _)| *u == c)?.1;
+                seconds += number.parse::<f64>().ok()? * factor;
+                number.clear();
+            }
+        }
+        Some(())
+    };
+
+    consume(date, &[('Y', 31_536_000.0), ('M', 2_592_000.0), ('W', 604_800.0), ('D', 86_400.0)])?;
+    consume(time, &[('H', 3600.0), ('M', 60.0), ('S', 1.0)])?;
+    Some(seconds)
+}
+
+/// Parse a `start-end` byte range attribute
+fn parse_range(value: &str) -> Option<(u64, u64)> {
+    let (start, end) = value.split_once('-')?;
+    Some((start.trim().parse().ok()?, end.trim().parse().ok()?))
+}
+
+/// Expand `$RepresentationID$`, `$Number%05d$`, `$Time$` and `$Bandwidth$` identifiers
+fn fill_template(template: &str, id: &str, number: u64, time: u64, bandwidth: u64) -> String {
+    let mut out = String::new();
+    let mut parts = template.split('$');
+
+    if let Some(first) = parts.next() {
+        out.push_str(first);
+    }
+
+    // Identifiers sit between pairs of `$`, so odd-indexed parts are identifiers
+    let mut in_identifier = true;
+    for part in parts {
+        if in_identifier {
+            let (name, width) = match part.split_once('%') {
+                Some((name, fmt)) => (name, fmt.trim_start_matches('0').trim_end_matches('d').parse().unwrap_or(0)),
+                None => (part, 0),
+            };
+            let value = match name {
+                "" => "$".to_string(),
+                "RepresentationID" => id.to_string(),
+                "Number" => format!("{:0width$}", number, width = width),
+                "Time" => format!("{:0width$}", time, width = width),
+                "Bandwidth" => format!("{:0width$}", bandwidth, width = width),
+                other => format!("${}$", other),
+            };
+            out.push_str(&value);
+        } else {
+            out.push_str(part);
+        }
+        in_identifier = !in_identifier;
+    }
+
+    out
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|c| c.has_tag_name(name))
+}
+
+/// Resolve the `BaseURL` children of each ancestor in turn, starting from the manifest URL
+fn base_url(base: &Url, nodes: &[Node]) -> Url {
+    nodes.iter().fold(base.clone(), |url, node| {
+        child(*node, "BaseURL")
+            .and_then(|b| b.text())
+            .and_then(|text| url.join(text.trim()).ok())
+            .unwrap_or(url)
+    })
+}
+
+/// Look up an attribute on the innermost node that defines it
+fn inherited<'a>(nodes: &[Node<'a, 'a>], attr: &str) -> Option<&'a str> {
+    nodes.iter().rev().find_map(|n| n.attribute(attr))
+}
+
+/// Build the segment list of a representation from its SegmentTemplate, SegmentList or
+/// SegmentBase, looking at the Representation, AdaptationSet and Period in that order
+fn resolve_segments(
+    hierarchy: &[Node],
+    base: &Url,
+    id: &str,
+    bandwidth: u64,
+    period_duration: Option<f64>,
+) -> Result<(Option<SegmentRef>, Vec<SegmentRef>)> {
+    let join = |path: &str| {
+        base.join(path).map_err(|_| Error::OutputParsing(format!("Invalid segment URL: {}", path)))
+    };
+
+    let templates: Vec<Node> = hierarchy.iter().filter_map(|n| child(*n, "SegmentTemplate")).collect();
+    if !templates.is_empty() {
+        let timescale: u64 = inherited(&templates, "timescale").and_then(|t| t.parse().ok()).unwrap_or(1);
+        let start_number: u64 = inherited(&templates, "startNumber").and_then(|n| n.parse().ok()).unwrap_or(1);
+        let media = inherited(&templates, "media")
+            .ok_or_else(|| Error::OutputParsing("SegmentTemplate without media attribute".into()))?;
+
+        let init = inherited(&templates, "initialization")
+            .map(|tmpl| join(&fill_template(tmpl, id, start_number, 0, bandwidth)))
+            .transpose()?
+            .map(|url| SegmentRef { url, byte_range: None });
+
+        let mut segments = Vec::new();
+        let timeline = templates.iter().rev().find_map(|t| child(*t, "SegmentTimeline"));
+
+        if let Some(timeline) = timeline {
+            let period_end = period_duration.map(|d| (d * timescale as f64) as u64);
+            let entries: Vec<Node> = timeline.children().filter(|c| c.has_tag_name("S")).collect();
+            let mut time = 0;
+            let mut number = start_number;
+
+            for (index, s) in entries.iter().enumerate() {
+                let duration: u64 = s.attribute("d").and_then(|d| d.parse().ok())
+                    .ok_or_else(|| Error::OutputParsing("SegmentTimeline entry without duration".into()))?;
+                if let Some(t) = s.attribute("t").and_then(|t| t.parse().ok()) {
+                    time = t;
+                }
+
+                // A negative repeat count repeats until the next entry or the end of the period
+                let repeat: i64 = s.attribute("r").and_then(|r| r.parse().ok()).unwrap_or(0);
+                let count = if repeat >= 0 {
+                    repeat as u64 + 1
+                } else {
+                    let end = entries.get(index + 1)
+                        .and_then(|next| next.attribute("t"))
+                        .and_then(|t| t.parse::<u64>().ok())
+                        .or(period_end)
+                        .unwrap_or(time + duration);
+                    end.saturating_sub(time).div_ceil(duration.max(1)).min(MAX_TEMPLATE_SEGMENTS)
+                };
+
+                for _ in 0..count {
+                    segments.push(SegmentRef {
+                        url: join(&fill_template(media, id, number, time, bandwidth))?,
+                        byte_range: None,
+                    });
+                    time += duration;
+                    number += 1;
+                }
+            }
+        } else {
+            let duration: f64 = inherited(&templates, "duration").and_then(|d| d.parse().ok())
+                .ok_or_else(|| Error::OutputParsing("SegmentTemplate without duration or timeline".into()))?;
+            let total = period_duration
+                .ok_or_else(|| Error::OutputParsing("Cannot count segments without a period duration".into()))?;
+            let count = ((total * timescale as f64) / duration).ceil() as u64;
+
+            for i in 0..count.min(MAX_TEMPLATE_SEGMENTS) {
+                let number = start_number + i;
+                let time = (i as f64 * duration) as u64;
+                segments.push(SegmentRef {
+                    url: join(&fill_template(media, id, number, time, bandwidth))?,
+                    byte_range: None,
+                });
+            }
+        }
+
+        return Ok((init, segments));
+    }
+
+    if let Some(list) = hierarchy.iter().rev().find_map(|n| child(*n, "SegmentList")) {
+        let init = child(list, "Initialization")
+            .map(|i| -> Result<SegmentRef> {
+                Ok(SegmentRef {
+                    url: i.attribute("sourceURL").map(join).transpose()?.unwrap_or_else(|| base.clone()),
+                    byte_range: i.attribute("range").and_then(parse_range),
+                })
+            })
+            .transpose()?;
+
+        let segments = list.children()
+            .filter(|c| c.has_tag_name("SegmentURL"))
+            .map(|s| -> Result<SegmentRef> {
+                Ok(SegmentRef {
+                    url: s.attribute("media").map(join).transpose()?.unwrap_or_else(|| base.clone()),
+                    byte_range: s.attribute("mediaRange").and_then(parse_range),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        return Ok((init, segments));
+    }
+
+    // SegmentBase, or a bare BaseURL: the representation is a single self-contained file
+    Ok((None, vec![SegmentRef { url: base.clone(), byte_range: None }]))
+}
+
+/// Parse an MPD manifest, resolving segment URLs against `base`
+pub fn parse_manifest(text: &str, base: &Url) -> Result<Manifest> {
+    let doc = Document::parse(text)
+        .map_err(|e| Error::OutputParsing(format!("Invalid MPD manifest: {}", e)))?;
+    let mpd = doc.root_element();
+
+    if !mpd.has_tag_name("MPD") {
+        return Err(Error::OutputParsing("Not an MPD manifest".into()));
+    }
+    if mpd.attribute("type") == Some("dynamic") {
+        return Err(Error::Platform("Live DASH streams are not supported".into()));
+    }
+
+    let duration = mpd.attribute("mediaPresentationDuration").and_then(parse_duration);
+    let mut representations = Vec::new();
+
+    // Only the first period is used; multi-period manifests are typically ads or live
+    let period = child(mpd, "Period")
+        .ok_or_else(|| Error::OutputParsing("MPD manifest has no Period".into()))?;
+    let period_duration = period.attribute("duration").and_then(parse_duration).or(duration);
+
+    for adaptation in period.children().filter(|c| c.has_tag_name("AdaptationSet")) {
+        for rep in adaptation.children().filter(|c| c.has_tag_name("Representation")) {
+            let hierarchy = [period, adaptation, rep];
+            let id = rep.attribute("id").unwrap_or_default().to_string();
+            let bandwidth = rep.attribute("bandwidth").and_then(|b| b.parse().ok()).unwrap_or(0);
+            let mime_type = inherited(&hierarchy, "mimeType").unwrap_or_default().to_string();
+            let content_type = adaptation.attribute("contentType").unwrap_or_default();
+
+            let kind = if mime_type.starts_with("video") || content_type == "video" {
+                StreamKind::Video
+            } else if mime_type.starts_with("audio") || content_type == "audio" {
+                StreamKind::Audio
+            } else {
+                StreamKind::Other
+            };
+
+            let base = base_url(base, &[mpd, period, adaptation, rep]);
+            let (init, segments) = resolve_segments(&hierarchy, &base, &id, bandwidth, period_duration)?;
+
+            representations.push(Representation {
+                id,
+                kind,
+                mime_type,
+                codecs: inherited(&hierarchy, "codecs").map(|c| c.to_string()),
+                bandwidth,
+                width: inherited(&hierarchy, "width").and_then(|w| w.parse().ok()),
+                height: inherited(&hierarchy, "height").and_then(|h| h.parse().ok()),
+                frame_rate: inherited(&hierarchy, "frameRate").and_then(parse_frame_rate),
+                language: adaptation.attribute("lang").map(|l| l.to_string()),
+                init,
+                segments,
+            });
+        }
+    }
+
+    Ok(Manifest { duration, representations })
+}
+
+/// Parse a frame rate given either as a number or a fraction like `30000/1001`
+fn parse_frame_rate(value: &str) -> Option<f64> {
+    match value.split_once('/') {
+        Some((num, den)) => Some(num.parse::<f64>().ok()? / den.parse::<f64>().ok()?),
+        None => value.parse().ok(),
+    }
+}
+
+/// Fetches and downloads DASH streams
+pub struct DashDownloader<'a> {
+    client: &'a Client,
+    headers: header::HeaderMap,
+    concurrency: usize,
+}
+
+impl<'a> DashDownloader<'a> {
+    pub fn new(client: &'a Client, headers: header::HeaderMap, concurrency: usize) -> Self {
+        Self {
+            client,
+            headers,
+            concurrency: concurrency.max(1),
+        }
+    }
+
+    async fn fetch(&self, segment: &SegmentRef) -> Result<Bytes> {
+        let mut request = self.client.get(segment.url.clone()).headers(self.headers.clone());
+        if let Some((start, end)) = segment.byte_range {
+            request = request.header(header::RANGE, format!("bytes={}-{}", start, end));
+        }
+
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(Error::DownloadFailed {
+                reason: format!("Server responded with {} for {}", response.status(), segment.url),
+            });
+        }
+        Ok(response.bytes().await?)
+    }
+
+    /// Fetch and parse the manifest at `url`
+    pub async fn fetch_manifest(&self, url: &Url) -> Result<Manifest> {
+        let response = self.client.get(url.clone()).headers(self.headers.clone()).send().await?;
+        if !response.status().is_success() {
+            return Err(Error::DownloadFailed {
+                reason: format!("Server responded with {} for {}", response.status(), url),
+            });
+        }
+        parse_manifest(&response.text().await?, url)
+    }
+
+    /// Download the given representations and mux them into `output_path` with ffmpeg.
+    ///
+    /// Each representation is fetched segment by segment, concurrently but written in order.
+    /// A single representation is remuxed on its own; without ffmpeg it is written as is.
+    pub async fn download(
+        &self,
+        representations: &[&Representation],
+        output_path: &Path,
+        progress_tx: Arc<watch::Sender<f64>>,
+    ) -> Result<()> {
+        if representations.is_empty() {
+            return Err(Error::NoSuitableFormats);
+        }
+
+        let total: usize = representations.iter().map(|r| r.segments.len()).sum();
+        let completed = AtomicUsize::new(0);
+        let report = || {
+            let done = completed.fetch_add(1, Ordering::Relaxed) + 1;
+            let _ = progress_tx.send(done as f64 / total.max(1) as f64);
+        };
+
+        let mut paths = Vec::new();
+        let mut result = Ok(());
+        for representation in representations {
+            let path = temp_path(output_path, &representation.id);
+            paths.push(path.clone());
+            debug!("Downloading DASH representation {} ({} segments)", representation.id, representation.segments.len());
+            result = self.download_representation(representation, &path, &report).await;
+            if result.is_err() {
+                break;
+            }
+        }
+
+        if result.is_ok() {
+            result = mux(&paths, output_path).await;
+        }
+
+        for path in &paths {
+            let _ = fs::remove_file(path).await;
+        }
+        result
+    }
+
+    async fn download_representation(
+        &self,
+        representation: &Representation,
+        path: &Path,
+        report: &(dyn Fn() + Sync),
+    ) -> Result<()> {
+        let mut file = fs::File::create(path).await?;
+
+        if let Some(init) = &representation.init {
+            file.write_all(&self.fetch(init).await?).await?;
+        }
+
+        let fetches: Vec<_> = representation.segments.iter()
+            .map(|segment| self.fetch_segment(segment, report))
+            .collect();
+        let mut segments = stream::iter(fetches).buffered(self.concurrency);
+
+        while let Some(data) = segments.try_next().await? {
+            file.write_all(&data).await?;
+        }
+
+        file.flush().await?;
+        Ok(())
+    }
+
+    async fn fetch_segment(&self, segment: &SegmentRef, report: &(dyn Fn() + Sync)) -> Result<Bytes> {
+        let data = self.fetch(segment).await?;
+        report();
+        Ok(data)
+    }
+}
+
+fn temp_path(output_path: &Path, representation_id: &str) -> PathBuf {
+    let mut name = output_path.as_os_str().to_os_string();
+    name.push(format!(".{}.part", sanitize_filename::sanitize(representation_id)));
+    PathBuf::from(name)
+}
+
+/// Mux the downloaded representations into the output container
+async fn mux(inputs: &[PathBuf], output_path: &Path) -> Result<()> {
+    if !ffmpeg::is_available().await {
+        if inputs.len() > 1 {
+            warn!("ffmpeg is not available; keeping only the first DASH stream");
+        }
+        fs::rename(&inputs[0], output_path).await?;
+        return Ok(());
+    }
+
+    let mut args: Vec<&OsStr> = Vec::new();
+    for input in inputs {
+        args.extend([OsStr::new("-i"), input.as_os_str()]);
+    }
+    let maps: Vec<String> = (0..inputs.len()).map(|i| i.to_string()).collect();
+    for map in &maps {
+        args.extend([OsStr::new("-map"), OsStr::new(map)]);
+    }
+    args.extend(["-c", "copy"].map(OsStr::new));
+    args.push(output_path.as_os_str());
+
+    ffmpeg::run(args).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT0M10.0S">
+  <BaseURL>media/</BaseURL>
+  <Period>
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <SegmentTemplate timescale="1000" initialization="$RepresentationID$/init.mp4" media="$RepresentationID$/seg-$Number%03d$.m4s" startNumber="1">
+        <SegmentTimeline>
+          <S t="0" d="4000" r="1"/>
+          <S d="2000"/>
+        </SegmentTimeline>
+      </SegmentTemplate>
+      <Representation id="720" bandwidth="2000000" width="1280" height="720" codecs="avc1.64001f" frameRate="30000/1001"/>
+      <Representation id="1080" bandwidth="4000000" width="1920" height="1080" codecs="avc1.640028"/>
+    </AdaptationSet>
+    <AdaptationSet contentType="audio" mimeType="audio/mp4" lang="en">
+      <Representation id="audio" bandwidth="128000" codecs="mp4a.40.2">
+        <BaseURL>audio.mp4</BaseURL>
+        <SegmentBase indexRange="800-1000"><Initialization range="0-799"/></SegmentBase>
+      </Representation>
+    </AdaptationSet>
+    <AdaptationSet mimeType="video/webm">
+      <Representation id="list" bandwidth="500000" height="360">
+        <SegmentList>
+          <Initialization sourceURL="list/init.webm"/>
+          <SegmentURL media="list/a.webm"/>
+          <SegmentURL media="list/all.webm" mediaRange="100-199"/>
+        </SegmentList>
+      </Representation>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+
+    #[test]
+    fn test_parse_manifest() {
+        let base = Url::parse("https://example.com/v/manifest.mpd").unwrap();
+        let manifest = parse_manifest(MANIFEST, &base).unwrap();
+
+        assert_eq!(manifest.duration, Some(10.0));
+        assert_eq!(manifest.representations.len(), 4);
+
+        let hd = manifest.representation("720").unwrap();
+        assert_eq!(hd.kind, StreamKind::Video);
+        assert_eq!(hd.height, Some(720));
+        assert!((hd.frame_rate.unwrap() - 29.97).abs() < 0.01);
+        assert_eq!(hd.init.as_ref().unwrap().url.as_str(), "https://example.com/v/media/720/init.mp4");
+        let urls: Vec<_> = hd.segments.iter().map(|s| s.url.as_str()).collect();
+        assert_eq!(urls, [
+            "https://example.com/v/media/720/seg-001.m4s",
+            "https://example.com/v/media/720/seg-002.m4s",
+            "https://example.com/v/media/720/seg-003.m4s",
+        ]);
+
+        let audio = manifest.representation("audio").unwrap();
+        assert_eq!(audio.kind, StreamKind::Audio);
+        assert_eq!(audio.language.as_deref(), Some("en"));
+        assert_eq!(audio.segments.len(), 1);
+        assert_eq!(audio.segments[0].url.as_str(), "https://example.com/v/media/audio.mp4");
+
+        let list = manifest.representation("list").unwrap();
+        assert_eq!(list.init.as_ref().unwrap().url.as_str(), "https://example.com/v/media/list/init.webm");
+        assert_eq!(list.segments[1].byte_range, Some((100, 199)));
+
+        let selected: Vec<_> = manifest.select(Some(&Quality::HD720)).iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(selected, ["720", "audio"]);
+    }
+
+    #[test]
+    fn test_fill_template_and_duration() {
+        assert_eq!(fill_template("$RepresentationID$/$Number%05d$-$Time$.m4s", "v1", 7, 9000, 0), "v1/00007-9000.m4s");
+        assert_eq!(fill_template("a$$b", "v1", 1, 0, 0), "a$b");
+        assert_eq!(parse_duration("PT1H2M3.5S"), Some(3723.5));
+        assert_eq!(parse_duration("P1DT1S"), Some(86401.0));
+    }
+}
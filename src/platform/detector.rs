@@ -3,6 +3,7 @@ use crate::platform::PlatformFactory;
 use crate::platform::Platform;
 use crate::Result;
 use crate::Error;
+use crate::Config;
 use url::Url;
 
 #[derive(Clone)]
@@ -12,16 +13,26 @@ pub struct PlatformDetector {
 
 impl PlatformDetector {
     pub fn new() -> Self {
-        let mut detector = Self {
-            platforms: Vec::new(),
-        };
+        Self::with_config(&Config::default())
+    }
+    
+    /// Create a detector whose platforms are configured from `config`
+    pub fn with_config(config: &Config) -> Self {
+        let mut detector = Self::empty();
         
         // Use the factory to register all supported platforms
-        PlatformFactory::register_platforms(&mut detector);
+        PlatformFactory::register_platforms(&mut detector, config);
         
         detector
     }
     
+    /// Create a detector without any registered platforms
+    pub fn empty() -> Self {
+        Self {
+            platforms: Vec::new(),
+        }
+    }
+
     /// Register a new platform implementation
     pub fn register(&mut self, platform: Arc<dyn Platform>) {
         self.platforms.push(platform);
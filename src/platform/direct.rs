@@ -0,0 +1,202 @@
+use async_trait::async_trait;
+use std::path::Path;
+use std::sync::Arc;
+use reqwest::{header, Client};
+use tokio::sync::watch;
+use url::Url;
+
+use super::dash::{self, DashDownloader, StreamKind};
+use super::hls::{self, HlsDownloader, Playlist};
+use super::{Format, Platform, Quality, VideoFormat, VideoInfo};
+use crate::{Config, Error, Result};
+use crate::selector::quality_height;
+
+/// Streams addressed directly by their manifest URL, such as `https://cdn.example.com/master.m3u8`
+/// or `https://cdn.example.com/manifest.mpd`
+pub struct DirectStream {
+    client: Client,
+    connections: usize,
+    /// Tallest variant to pick for `best`, from the configured quality
+    max_height: Option<u32>,
+}
+
+impl Default for DirectStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DirectStream {
+    pub fn new() -> Self {
+        Self::with_config(&Config::default())
+    }
+
+    /// Create a direct stream platform using the download settings from `config`
+    pub fn with_config(config: &Config) -> Self {
+        Self {
+            client: Client::new(),
+            connections: config.connections,
+            max_height: quality_height(&config.default_quality),
+        }
+    }
+
+    fn is_hls_url(url: &Url) -> bool {
+        url.path().to_lowercase().ends_with(".m3u8")
+    }
+
+    fn is_dash_url(url: &Url) -> bool {
+        url.path().to_lowercase().ends_with(".mpd")
+    }
+
+    /// Use the manifest's file name as the title
+    fn title_from_url(url: &Url) -> String {
+        url.path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .and_then(|name| name.rsplit_once('.').map(|(stem, _)| stem.to_string()))
+            .filter(|stem| !stem.is_empty())
+            .unwrap_or_else(|| "stream".to_string())
+    }
+
+    fn hls(&self) -> HlsDownloader<'_> {
+        HlsDownloader::new(&self.client, header::HeaderMap::new(), self.connections)
+    }
+
+    fn dash(&self) -> DashDownloader<'_> {
+        DashDownloader::new(&self.client, header::HeaderMap::new(), self.connections)
+    }
+
+    async fn extract_dash_info(&self, url: &Url) -> Result<VideoInfo> {
+        let manifest = self.dash().fetch_manifest(url).await?;
+        let formats = manifest.representations.iter()
+            .filter(|r| r.kind != StreamKind::Other)
+            .map(|r| dash::to_video_format(r, manifest.duration))
+            .collect();
+
+        Ok(VideoInfo {
+            url: url.clone(),
+            id: None,
+            platform: self.name().to_string(),
+            webpage_url: None,
+            title: Self::title_from_url(url),
+            description: None,
+            duration: manifest.duration.map(|d| d as u64),
+            formats,
+            subtitles: Vec::new(),
+            thumbnails: Vec::new(),
+            uploader: None,
+            uploader_id: None,
+            upload_date: None,
+            view_count: None,
+            like_count: None,
+            comment_count: None,
+            tags: Vec::new(),
+            categories: Vec::new(),
+            chapters: Vec::new(),
+            section: None,
+        })
+    }
+
+    /// Download the representations of a DASH manifest selected by `format_id`
+    async fn download_dash(
+        &self,
+        url: &Url,
+        format_id: &str,
+        output_path: &Path,
+        progress_tx: Arc<watch::Sender<f64>>,
+    ) -> Result<()> {
+        let manifest = self.dash().fetch_manifest(url).await?;
+
+        let selected = manifest.select_formats(format_id)?;
+
+        self.dash().download(&selected, output_path, progress_tx).await
+    }
+}
+
+#[async_trait]
+impl Platform for DirectStream {
+    fn name(&self) -> &'static str {
+        "Direct"
+    }
+
+    fn supports_url(&self, url: &Url) -> bool {
+        Self::is_hls_url(url) || Self::is_dash_url(url)
+    }
+
+    async fn extract_info(&self, url: &Url) -> Result<VideoInfo> {
+        if Self::is_dash_url(url) {
+            return self.extract_dash_info(url).await;
+        }
+
+        let (formats, duration) = match self.hls().fetch_playlist(url).await? {
+            Playlist::Master(master) => {
+                let formats = master.variants.iter()
+                    .zip(hls::variant_format_ids(&master.variants))
+                    .map(|(variant, id)| hls::to_video_format(variant, id))
+                    .collect();
+                (formats, None)
+            }
+            Playlist::Media(media) => {
+                let format = VideoFormat::new("hls", Quality::Medium, Format::MP4);
+                (vec![format], Some(media.duration() as u64))
+            }
+        };
+
+        Ok(VideoInfo {
+            url: url.clone(),
+            id: None,
+            platform: self.name().to_string(),
+            webpage_url: None,
+            title: Self::title_from_url(url),
+            description: None,
+            duration,
+            formats,
+            subtitles: Vec::new(),
+            thumbnails: Vec::new(),
+            uploader: None,
+            uploader_id: None,
+            upload_date: None,
+            view_count: None,
+            like_count: None,
+            comment_count: None,
+            tags: Vec::new(),
+            categories: Vec::new(),
+            chapters: Vec::new(),
+            section: None,
+        })
+    }
+
+    async fn download_video(
+        &self,
+        info: &VideoInfo,
+        format_id: &str,
+        output_path: &Path,
+        progress_tx: Arc<watch::Sender<f64>>,
+    ) -> Result<()> {
+        if Self::is_dash_url(&info.url) {
+            return self.download_dash(&info.url, format_id, output_path, progress_tx).await;
+        }
+
+        if format_id == "best" || format_id == "hls" {
+            let quality = self.max_height.map(|height| Quality::Custom(height.to_string()));
+            return self.hls().download(&info.url, quality.as_ref(), output_path, progress_tx).await;
+        }
+
+        if !info.formats.iter().any(|f| f.id == format_id) {
+            return Err(Error::InvalidFormat(format_id.to_string()));
+        }
+
+        self.hls()
+            .download_variant(
+                &info.url,
+                |variants| {
+                    variants.iter()
+                        .zip(hls::variant_format_ids(variants))
+                        .find(|(_, id)| id == format_id)
+                        .map(|(variant, _)| variant)
+                },
+                output_path,
+                progress_tx,
+            )
+            .await
+    }
+}
@@ -0,0 +1,670 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+use bytes::Bytes;
+use futures_util::stream::{self, StreamExt, TryStreamExt};
+use log::{debug, warn};
+use reqwest::{header, Client};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::watch;
+use url::Url;
+
+use super::{split_codecs, Format, Quality, VideoFormat};
+use crate::utils::ffmpeg;
+use crate::{Error, Result};
+
+type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+
+/// A variant stream listed in a master playlist
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub uri: Url,
+    pub bandwidth: u64,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub frame_rate: Option<f64>,
+    pub codecs: Option<String>,
+    pub audio_group: Option<String>,
+    /// `SDR`, `PQ` or `HLG`
+    pub video_range: Option<String>,
+}
+
+/// An alternate rendition (`#EXT-X-MEDIA`) listed in a master playlist
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rendition {
+    pub media_type: String,
+    pub group_id: String,
+    pub name: Option<String>,
+    pub language: Option<String>,
+    pub uri: Option<Url>,
+    pub default: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MasterPlaylist {
+    pub variants: Vec<Variant>,
+    pub renditions: Vec<Rendition>,
+}
+
+/// Byte range of a resource, as `(length, offset)`
+pub type ByteRange = (u64, u64);
+
+/// Encryption applied to the segments that follow an `#EXT-X-KEY` tag
+#[derive(Debug, Clone, PartialEq)]
+pub struct Key {
+    pub method: String,
+    pub uri: Option<Url>,
+    pub iv: Option<[u8; 16]>,
+}
+
+/// The initialization section (`#EXT-X-MAP`) of fragmented MP4 playlists
+#[derive(Debug, Clone, PartialEq)]
+pub struct InitSection {
+    pub uri: Url,
+    pub byte_range: Option<ByteRange>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub uri: Url,
+    pub duration: f64,
+    pub sequence: u64,
+    pub byte_range: Option<ByteRange>,
+    pub key: Option<Key>,
+    pub init: Option<InitSection>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaPlaylist {
+    pub segments: Vec<Segment>,
+    pub end_list: bool,
+}
+
+impl MediaPlaylist {
+    /// Total duration of all segments in seconds
+    pub fn duration(&self) -> f64 {
+        self.segments.iter().map(|s| s.duration).sum()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Playlist {
+    Master(MasterPlaylist),
+    Media(MediaPlaylist),
+}
+
+/// Split an attribute list such as `BANDWIDTH=1280000,CODECS="avc1,mp4a"` into pairs
+fn parse_attributes(input: &str) -> HashMap<String, String> {
+    let mut attributes = HashMap::new();
+    let mut rest = input.trim();
+
+    while !rest.is_empty() {
+        let Some((name, after)) = rest.split_once('=') else { break };
+        let (value, remaining) = if let Some(quoted) = after.strip_prefix('"') {
+            match quoted.split_once('"') {
+                Some((value, remaining)) => (value, remaining),
+                None => (quoted, ""),
+            }
+        } else {
+            match after.split_once(',') {
+                Some((value, remaining)) => (value, remaining),
+                None => (after, ""),
+            }
+        };
+
+        attributes.insert(name.trim().to_uppercase(), value.to_string());
+        rest = remaining.trim_start_matches(',').trim();
+    }
+
+    attributes
+}
+
+/// Parse an `#EXT-X-BYTERANGE` value `length[@offset]`; a missing offset continues from `next_offset`
+fn parse_byte_range(value: &str, next_offset: u64) -> Result<ByteRange> {
+    let parsed = match value.split_once('@') {
+        Some((length, offset)) => length.trim().parse().ok().zip(offset.trim().parse().ok()),
+        None => value.trim().parse().ok().map(|length| (length, next_offset)),
+    };
+    // An empty range has no last byte to request
+    parsed
+        .filter(|(length, _)| *length > 0)
+        .ok_or_else(|| Error::OutputParsing(format!("Invalid HLS byte range: {}", value)))
+}
+
+fn parse_iv(value: &str) -> Option<[u8; 16]> {
+    let hex = value.strip_prefix("0x").or_else(|| value.strip_prefix("0X"))?;
+    let number = u128::from_str_radix(hex, 16).ok()?;
+    Some(number.to_be_bytes())
+}
+
+/// Parse an M3U8 playlist, resolving relative URIs against `base`
+pub fn parse_playlist(text: &str, base: &Url) -> Result<Playlist> {
+    let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+    if lines.next() != Some("#EXTM3U") {
+        return Err(Error::OutputParsing("Not an M3U8 playlist".into()));
+    }
+
+    let resolve = |uri: &str| {
+        base.join(uri).map_err(|_| Error::OutputParsing(format!("Invalid URI in playlist: {}", uri)))
+    };
+
+    if text.contains("#EXT-X-STREAM-INF") {
+        let mut variants = Vec::new();
+        let mut renditions = Vec::new();
+        let mut pending: Option<HashMap<String, String>> = None;
+
+        for line in lines {
+            if let Some(attrs) = line.strip_prefix("#EXT-X-STREAM-INF:") {
+                pending = Some(parse_attributes(attrs));
+            } else if let Some(attrs) = line.strip_prefix("#EXT-X-MEDIA:") {
+                let attrs = parse_attributes(attrs);
+                renditions.push(Rendition {
+                    media_type: attrs.get("TYPE").cloned().unwrap_or_default(),
+                    group_id: attrs.get("GROUP-ID").cloned().unwrap_or_default(),
+                    name: attrs.get("NAME").cloned(),
+                    language: attrs.get("LANGUAGE").cloned(),
+                    uri: attrs.get("URI").map(|uri| resolve(uri)).transpose()?,
+                    default: attrs.get("DEFAULT").is_some_and(|v| v == "YES"),
+                });
+            } else if !line.starts_with('#') {
+                if let Some(attrs) = pending.take() {
+                    let resolution = attrs.get("RESOLUTION").and_then(|r| r.split_once('x'));
+                    variants.push(Variant {
+                        uri: resolve(line)?,
+                        bandwidth: attrs.get("BANDWIDTH").and_then(|b| b.parse().ok()).unwrap_or(0),
+                        width: resolution.and_then(|(w, _)| w.parse().ok()),
+                        height: resolution.and_then(|(_, h)| h.parse().ok()),
+                        frame_rate: attrs.get("FRAME-RATE").and_then(|f| f.parse().ok()),
+                        codecs: attrs.get("CODECS").cloned(),
+                        audio_group: attrs.get("AUDIO").cloned(),
+                        video_range: attrs.get("VIDEO-RANGE").cloned(),
+                    });
+                }
+            }
+        }
+
+        return Ok(Playlist::Master(MasterPlaylist { variants, renditions }));
+    }
+
+    let mut segments = Vec::new();
+    let mut sequence = 0;
+    let mut end_list = false;
+    let mut duration = 0.0;
+    let mut byte_range = None;
+    let mut key: Option<Key> = None;
+    let mut init = None;
+    // Where the next implicit byte range starts, per resource
+    let mut next_offsets: HashMap<Url, u64> = HashMap::new();
+    let mut pending_range: Option<String> = None;
+
+    for line in lines {
+        if let Some(value) = line.strip_prefix("#EXT-X-MEDIA-SEQUENCE:") {
+            sequence = value.parse().unwrap_or(0);
+        } else if let Some(value) = line.strip_prefix("#EXTINF:") {
+            duration = value.split(',').next().and_then(|d| d.parse().ok()).unwrap_or(0.0);
+        } else if let Some(value) = line.strip_prefix("#EXT-X-BYTERANGE:") {
+            pending_range = Some(value.to_string());
+        } else if let Some(attrs) = line.strip_prefix("#EXT-X-KEY:") {
+            let attrs = parse_attributes(attrs);
+            let method = attrs.get("METHOD").cloned().unwrap_or_else(|| "NONE".to_string());
+            key = if method == "NONE" {
+                None
+            } else {
+                Some(Key {
+                    method,
+                    uri: attrs.get("URI").map(|uri| resolve(uri)).transpose()?,
+                    iv: attrs.get("IV").and_then(|iv| parse_iv(iv)),
+                })
+            };
+        } else if let Some(attrs) = line.strip_prefix("#EXT-X-MAP:") {
+            let attrs = parse_attributes(attrs);
+            if let Some(uri) = attrs.get("URI") {
+                init = Some(InitSection {
+                    uri: resolve(uri)?,
+                    byte_range: attrs.get("BYTERANGE").map(|r| parse_byte_range(r, 0)).transpose()?,
+                });
+            }
+        } else if line == "#EXT-X-ENDLIST" {
+            end_list = true;
+        } else if !line.starts_with('#') {
+            let uri = resolve(line)?;
+            if let Some(range) = pending_range.take() {
+                let next = next_offsets.get(&uri).copied().unwrap_or(0);
+                let (length, offset) = parse_byte_range(&range, next)?;
+                next_offsets.insert(uri.clone(), offset + length);
+                byte_range = Some((length, offset));
+            }
+
+            segments.push(Segment {
+                uri,
+                duration,
+                sequence,
+                byte_range: byte_range.take(),
+                key: key.clone(),
+                init: init.clone(),
+            });
+            sequence += 1;
+            duration = 0.0;
+        }
+    }
+
+    Ok(Playlist::Media(MediaPlaylist { segments, end_list }))
+}
+
+/// Pick the variant that best matches the requested quality.
+///
+/// The highest-bandwidth variant not taller than the quality's height limit wins; if every
+/// variant is taller, the smallest one is used. `None` selects the best variant overall.
+pub fn select_variant<'a>(variants: &'a [Variant], quality: Option<&Quality>) -> Option<&'a Variant> {
+    let max_height = quality.and_then(|q| q.max_height());
+    let by_quality = |a: &&Variant, b: &&Variant| {
+        (a.height, a.bandwidth).cmp(&(b.height, b.bandwidth))
+    };
+
+    match max_height {
+        Some(limit) => variants.iter()
+            .filter(|v| v.height.is_none_or(|h| h <= limit))
+            .max_by(by_quality)
+            .or_else(|| variants.iter().min_by(by_quality)),
+        None => variants.iter().max_by(by_quality),
+    }
+}
+
+/// Format IDs used to refer to the variants of a master playlist, e.g. `hls-2176` for a
+/// 2176 kbps variant. Variants sharing a bitrate are told apart by their height, as in
+/// `hls-2176-720p`, and by their 1-based position when that is shared too.
+pub fn variant_format_ids(variants: &[Variant]) -> Vec<String> {
+    let shared = |ids: &[String], id: &String| ids.iter().filter(|other| *other == id).count() > 1;
+
+    let by_bitrate: Vec<String> = variants.iter()
+        .map(|variant| format!("hls-{}", variant.bandwidth / 1000))
+        .collect();
+    let by_height: Vec<String> = variants.iter()
+        .zip(&by_bitrate)
+        .map(|(variant, id)| match variant.height {
+            Some(height) if shared(&by_bitrate, id) => format!("{}-{}p", id, height),
+            _ => id.clone(),
+        })
+        .collect();
+
+    by_height.iter()
+        .enumerate()
+        .map(|(index, id)| match shared(&by_height, id) {
+            true => format!("{}-{}", id, index + 1),
+            false => id.clone(),
+        })
+        .collect()
+}
+
+/// Describe a variant as a format with the ID from [`variant_format_ids`]; audio from an
+/// alternate rendition is merged on download
+pub fn to_video_format(variant: &Variant, id: String) -> VideoFormat {
+    let (vcodec, acodec) = variant.codecs.as_deref().map(split_codecs).unwrap_or((None, None));
+    let has_audio = acodec.is_some() || variant.audio_group.is_some() || variant.codecs.is_none();
+
+    VideoFormat {
+        width: variant.width,
+        height: variant.height,
+        fps: variant.frame_rate,
+        hdr: variant.video_range.as_deref().is_some_and(|range| range != "SDR"),
+        vcodec,
+        acodec,
+        video_bitrate: Some((variant.bandwidth / 1000) as u32),
+        has_audio,
+        ..VideoFormat::new(
+            id,
+            variant.height.map(Quality::from_height).unwrap_or(Quality::Medium),
+            Format::MP4,
+        )
+    }
+}
+
+/// Fetches and downloads HLS streams
+pub struct HlsDownloader<'a> {
+    client: &'a Client,
+    headers: header::HeaderMap,
+    concurrency: usize,
+}
+
+impl<'a> HlsDownloader<'a> {
+    pub fn new(client: &'a Client, headers: header::HeaderMap, concurrency: usize) -> Self {
+        Self {
+            client,
+            headers,
+            concurrency: concurrency.max(1),
+        }
+    }
+
+    async fn fetch(&self, url: &Url, byte_range: Option<ByteRange>) -> Result<Bytes> {
+        let mut request = self.client.get(url.clone()).headers(self.headers.clone());
+        if let Some((length, offset)) = byte_range {
+            request = request.header(header::RANGE, format!("bytes={}-{}", offset, offset + length - 1));
+        }
+
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(Error::DownloadFailed {
+                reason: format!("Server responded with {} for {}", response.status(), url),
+            });
+        }
+        Ok(response.bytes().await?)
+    }
+
+    /// Fetch and parse the playlist at `url`
+    pub async fn fetch_playlist(&self, url: &Url) -> Result<Playlist> {
+        let body = self.fetch(url, None).await?;
+        parse_playlist(&String::from_utf8_lossy(&body), url)
+    }
+
+    /// Resolve a playlist URL to a media playlist, choosing a variant with `select` if it is a
+    /// master playlist. Also returns the chosen variant's separate audio rendition, if any.
+    pub async fn resolve<F>(&self, url: &Url, select: F) -> Result<(MediaPlaylist, Option<MediaPlaylist>)>
+    where
+        F: for<'v> Fn(&'v [Variant]) -> Option<&'v Variant>,
+    {
+        let master = match self.fetch_playlist(url).await? {
+            Playlist::Media(media) => return Ok((media, None)),
+            Playlist::Master(master) => master,
+        };
+
+        let variant = select(&master.variants).ok_or(Error::NoSuitableFormats)?;
+        debug!("Selected HLS variant {:?} at {} bps", variant.height, variant.bandwidth);
+
+        let video = match self.fetch_playlist(&variant.uri).await? {
+            Playlist::Media(media) => media,
+            Playlist::Master(_) => return Err(Error::OutputParsing("Nested master playlists are not supported".into())),
+        };
+
+        let audio_uri = variant.audio_group.as_ref().and_then(|group| {
+            let candidates: Vec<_> = master.renditions.iter()
+                .filter(|r| r.media_type == "AUDIO" && &r.group_id == group && r.uri.is_some())
+                .collect();
+            candidates.iter().find(|r| r.default).or(candidates.first()).and_then(|r| r.uri.clone())
+        });
+
+        let audio = match audio_uri {
+            Some(uri) => match self.fetch_playlist(&uri).await? {
+                Playlist::Media(media) => Some(media),
+                Playlist::Master(_) => None,
+            },
+            None => None,
+        };
+
+        Ok((video, audio))
+    }
+
+    /// Download the stream at `playlist_url` into `output_path`, picking the variant that
+    /// best matches `quality` (or the best one if `None`).
+    pub async fn download(
+        &self,
+        playlist_url: &Url,
+        quality: Option<&Quality>,
+        output_path: &Path,
+        progress_tx: Arc<watch::Sender<f64>>,
+    ) -> Result<()> {
+        self.download_variant(playlist_url, |variants| select_variant(variants, quality), output_path, progress_tx).await
+    }
+
+    /// Download the stream at `playlist_url` into `output_path`, picking the variant with `select`.
+    ///
+    /// Segments are fetched concurrently, decrypted when AES-128 encrypted, and concatenated in
+    /// playlist order. The result is remuxed into the output container with ffmpeg, merging
+    /// a separate audio rendition if the variant has one. Without ffmpeg the concatenated
+    /// stream is only kept when it already fits the output container.
+    pub async fn download_variant<F>(
+        &self,
+        playlist_url: &Url,
+        select: F,
+        output_path: &Path,
+        progress_tx: Arc<watch::Sender<f64>>,
+    ) -> Result<()>
+    where
+        F: for<'v> Fn(&'v [Variant]) -> Option<&'v Variant>,
+    {
+        let (video, audio) = self.resolve(playlist_url, select).await?;
+        if !video.end_list {
+            warn!("HLS playlist has no end marker; downloading the segments currently listed");
+        }
+
+        let total = video.segments.len() + audio.as_ref().map_or(0, |a| a.segments.len());
+        let completed = AtomicUsize::new(0);
+        let report = || {
+            let done = completed.fetch_add(1, Ordering::Relaxed) + 1;
+            let _ = progress_tx.send(done as f64 / total.max(1) as f64);
+        };
+
+        let video_path = temp_path(output_path, "video");
+        self.download_segments(&video, &video_path, &report).await?;
+
+        let audio_path = match &audio {
+            Some(audio) => {
+                let path = temp_path(output_path, "audio");
+                self.download_segments(audio, &path, &report).await?;
+                Some(path)
+            }
+            None => None,
+        };
+
+        let fragmented = video.segments.iter().any(|segment| segment.init.is_some());
+        let result = remux(&video_path, audio_path.as_deref(), output_path, fragmented).await;
+
+        let _ = fs::remove_file(&video_path).await;
+        if let Some(path) = &audio_path {
+            let _ = fs::remove_file(path).await;
+        }
+        result
+    }
+
+    /// Download every segment of a media playlist into one file, in order
+    async fn download_segments(&self, playlist: &MediaPlaylist, path: &Path, report: &(dyn Fn() + Sync)) -> Result<()> {
+        let keys = self.fetch_keys(playlist).await?;
+        let mut file = fs::File::create(path).await?;
+
+        // Fragmented MP4 streams need their initialization section before any segment
+        let mut last_init = None;
+
+        let fetches: Vec<_> = playlist.segments.iter()
+            .map(|segment| self.fetch_segment(segment, &keys, report))
+            .collect();
+        let mut segments = stream::iter(fetches).buffered(self.concurrency);
+
+        while let Some((segment, data)) = segments.try_next().await? {
+            if let Some(init) = segment.init.as_ref().filter(|init| last_init.as_ref() != Some(*init)) {
+                let init_data = self.fetch(&init.uri, init.byte_range).await?;
+                file.write_all(&init_data).await?;
+                last_init = Some(init.clone());
+            }
+            file.write_all(&data).await?;
+        }
+
+        file.flush().await?;
+        Ok(())
+    }
+
+    /// Fetch and decrypt a single segment
+    async fn fetch_segment<'s>(
+        &self,
+        segment: &'s Segment,
+        keys: &HashMap<Url, Bytes>,
+        report: &(dyn Fn() + Sync),
+    ) -> Result<(&'s Segment, Bytes)> {
+        let data = self.fetch(&segment.uri, segment.byte_range).await?;
+        let data = decrypt(segment, data, keys)?;
+        report();
+        Ok((segment, data))
+    }
+
+    /// Fetch every distinct AES-128 key referenced by the playlist
+    async fn fetch_keys(&self, playlist: &MediaPlaylist) -> Result<HashMap<Url, Bytes>> {
+        let mut keys = HashMap::new();
+        for key in playlist.segments.iter().filter_map(|s| s.key.as_ref()) {
+            if key.method != "AES-128" {
+                return Err(Error::Platform(format!("Unsupported HLS encryption method: {}", key.method)));
+            }
+            let uri = key.uri.as_ref()
+                .ok_or_else(|| Error::OutputParsing("AES-128 key without URI".into()))?;
+            if !keys.contains_key(uri) {
+                let data = self.fetch(uri, None).await?;
+                keys.insert(uri.clone(), data);
+            }
+        }
+        Ok(keys)
+    }
+}
+
+/// Decrypt an AES-128 segment; unencrypted segments are returned unchanged
+fn decrypt(segment: &Segment, data: Bytes, keys: &HashMap<Url, Bytes>) -> Result<Bytes> {
+    let Some(key) = &segment.key else { return Ok(data) };
+    let key_data = key.uri.as_ref()
+        .and_then(|uri| keys.get(uri))
+        .filter(|k| k.len() == 16)
+        .ok_or_else(|| Error::DownloadFailed { reason: "Missing or invalid AES-128 key".into() })?;
+
+    // Without an explicit IV the media sequence number is used, per RFC 8216
+    let iv = key.iv.unwrap_or_else(|| (segment.sequence as u128).to_be_bytes());
+
+    Aes128CbcDec::new(key_data.as_ref().into(), &iv.into())
+        .decrypt_padded_vec_mut::<Pkcs7>(&data)
+        .map(Bytes::from)
+        .map_err(|_| Error::DownloadFailed { reason: format!("Failed to decrypt segment {}", segment.uri) })
+}
+
+fn temp_path(output_path: &Path, stream: &str) -> PathBuf {
+    let mut name = output_path.as_os_str().to_os_string();
+    name.push(format!(".{}.part", stream));
+    PathBuf::from(name)
+}
+
+/// Remux the downloaded stream(s) into the output container.
+///
+/// Without ffmpeg the stream is moved into place as is, which only works when the output
+/// matches it: `.ts` for MPEG-TS segments, or an MP4 container for fragmented MP4.
+async fn remux(video: &Path, audio: Option<&Path>, output_path: &Path, fragmented: bool) -> Result<()> {
+    if !ffmpeg::is_available().await {
+        let extension = output_path.extension().and_then(OsStr::to_str).unwrap_or("").to_lowercase();
+        let fits = match fragmented {
+            true => ["mp4", "m4a", "m4v", "mov"].contains(&extension.as_str()),
+            false => extension == "ts",
+        };
+        if !fits {
+            return Err(Error::CommandExecution {
+                command: "ffmpeg".to_string(),
+                reason: format!(
+                    "ffmpeg is required to save this HLS stream as .{}; install it or download to a .ts file",
+                    extension
+                ),
+            });
+        }
+
+        if audio.is_some() {
+            warn!("ffmpeg is not available; the separate audio rendition is not merged");
+        }
+        fs::rename(video, output_path).await?;
+        return Ok(());
+    }
+
+    let mut args: Vec<&OsStr> = vec![OsStr::new("-i"), video.as_os_str()];
+    if let Some(audio) = audio {
+        args.extend([OsStr::new("-i"), audio.as_os_str()]);
+        args.extend(["-map", "0:v:0", "-map", "1:a:0"].map(OsStr::new));
+    }
+    args.extend(["-c", "copy"].map(OsStr::new));
+    if output_path.extension().is_some_and(|ext| ext == "mp4" || ext == "m4a" || ext == "mov") {
+        args.extend(["-bsf:a", "aac_adtstoasc"].map(OsStr::new));
+    }
+    args.push(output_path.as_os_str());
+
+    ffmpeg::run(args).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_master_playlist() {
+        let text = "#EXTM3U\n\
+            #EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aud\",NAME=\"English\",LANGUAGE=\"en\",DEFAULT=YES,URI=\"audio/en.m3u8\"\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=800000,RESOLUTION=640x360,CODECS=\"avc1.4d401e,mp4a.40.2\",AUDIO=\"aud\"\n\
+            360p.m3u8\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=5000000,RESOLUTION=1920x1080,FRAME-RATE=60.000\n\
+            https://cdn.example.com/1080p.m3u8\n";
+        let base = Url::parse("https://example.com/hls/master.m3u8").unwrap();
+
+        let Playlist::Master(master) = parse_playlist(text, &base).unwrap() else {
+            panic!("expected a master playlist");
+        };
+
+        assert_eq!(master.variants.len(), 2);
+        assert_eq!(master.variants[0].uri.as_str(), "https://example.com/hls/360p.m3u8");
+        assert_eq!(master.variants[0].codecs.as_deref(), Some("avc1.4d401e,mp4a.40.2"));
+        assert_eq!(master.variants[0].audio_group.as_deref(), Some("aud"));
+        assert_eq!(master.variants[1].height, Some(1080));
+        assert_eq!(master.variants[1].frame_rate, Some(60.0));
+
+        let format = to_video_format(&master.variants[0], "hls-800".to_string());
+        assert_eq!(format.vcodec.as_deref(), Some("avc1.4d401e"));
+        assert_eq!(format.acodec.as_deref(), Some("mp4a.40.2"));
+        assert_eq!(format.width, Some(640));
+        assert_eq!(master.renditions[0].uri.as_ref().unwrap().as_str(), "https://example.com/hls/audio/en.m3u8");
+        assert!(master.renditions[0].default);
+
+        let chosen = select_variant(&master.variants, Some(&Quality::HD720)).unwrap();
+        assert_eq!(chosen.height, Some(360));
+        let best = select_variant(&master.variants, None).unwrap();
+        assert_eq!(best.height, Some(1080));
+
+        // IDs stay unique when variants share a bitrate
+        let variant = |bandwidth, height| Variant { bandwidth, height, ..master.variants[0].clone() };
+        let variants = [
+            variant(2_500_000, Some(720)),
+            variant(2_500_000, Some(1080)),
+            variant(2_500_000, Some(1080)),
+            variant(800_000, Some(360)),
+        ];
+        assert_eq!(variant_format_ids(&variants), ["hls-2500-720p", "hls-2500-1080p-2", "hls-2500-1080p-3", "hls-800"]);
+    }
+
+    #[test]
+    fn test_parse_media_playlist() {
+        let text = "#EXTM3U\n\
+            #EXT-X-TARGETDURATION:6\n\
+            #EXT-X-MEDIA-SEQUENCE:7\n\
+            #EXT-X-KEY:METHOD=AES-128,URI=\"key.bin\",IV=0x0000000000000000000000000000000A\n\
+            #EXTINF:6.0,\n\
+            #EXT-X-BYTERANGE:1000@0\n\
+            media.ts\n\
+            #EXTINF:4.5,\n\
+            #EXT-X-BYTERANGE:500\n\
+            media.ts\n\
+            #EXT-X-KEY:METHOD=NONE\n\
+            #EXTINF:2.0,\n\
+            tail.ts\n\
+            #EXT-X-ENDLIST\n";
+        let base = Url::parse("https://example.com/hls/720p.m3u8").unwrap();
+
+        let Playlist::Media(media) = parse_playlist(text, &base).unwrap() else {
+            panic!("expected a media playlist");
+        };
+
+        assert!(media.end_list);
+        assert_eq!(media.segments.len(), 3);
+        assert_eq!(media.duration(), 12.5);
+        assert_eq!(media.segments[0].sequence, 7);
+        assert_eq!(media.segments[0].byte_range, Some((1000, 0)));
+        assert_eq!(media.segments[1].byte_range, Some((500, 1000)));
+        let key = media.segments[1].key.as_ref().unwrap();
+        assert_eq!(key.uri.as_ref().unwrap().as_str(), "https://example.com/hls/key.bin");
+        assert_eq!(key.iv.unwrap()[15], 10);
+        assert!(media.segments[2].key.is_none());
+        assert_eq!(media.segments[2].byte_range, None);
+
+        // A zero-length range has no bytes to request
+        let empty = "#EXTM3U\n#EXTINF:1.0,\n#EXT-X-BYTERANGE:0@10\nmedia.ts\n";
+        assert!(parse_playlist(empty, &base).is_err());
+    }
+}
@@ -0,0 +1,430 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use futures_util::StreamExt;
+use futures_util::future::try_join_all;
+use log::debug;
+use reqwest::{header, Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::watch;
+
+use crate::{Error, Result};
+
+/// Files smaller than this are always fetched over a single connection
+const MIN_SEGMENT_SIZE: u64 = 1024 * 1024;
+
+/// Sidecar state stored next to a `.part` file so a later run can resume it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct PartState {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    total_size: Option<u64>,
+    /// Number of byte ranges the download was split into, 0 for a single stream
+    #[serde(default)]
+    segments: usize,
+}
+
+impl PartState {
+    /// Build the state describing a response's entity
+    fn from_response(url: &str, response: &reqwest::Response, total_size: Option<u64>) -> Self {
+        let header_str = |name: header::HeaderName| {
+            response.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|s| s.to_string())
+        };
+
+        Self {
+            url: url.to_string(),
+            etag: header_str(header::ETAG),
+            last_modified: header_str(header::LAST_MODIFIED),
+            total_size,
+            segments: 0,
+        }
+    }
+
+    /// Whether the server's entity still matches the one the partial file was started from
+    fn matches(&self, other: &PartState) -> bool {
+        match (&self.etag, &other.etag) {
+            (Some(a), Some(b)) => return a == b,
+            (Some(_), None) | (None, Some(_)) => return false,
+            (None, None) => {}
+        }
+        match (&self.last_modified, &other.last_modified) {
+            (Some(a), Some(b)) => a == b,
+            (None, None) => self.total_size.is_some() && self.total_size == other.total_size,
+            _ => false,
+        }
+    }
+
+    /// Value for the `If-Range` header, preferring the strong ETag validator
+    fn if_range(&self) -> Option<&str> {
+        self.etag.as_deref()
+            .filter(|etag| !etag.starts_with("W/"))
+            .or(self.last_modified.as_deref())
+    }
+}
+
+/// Path of the partial download for an output file
+pub fn part_path(output_path: &Path) -> PathBuf {
+    let mut name = output_path.as_os_str().to_os_string();
+    name.push(".part");
+    PathBuf::from(name)
+}
+
+/// Path of the partial file holding one byte range of a segmented download
+fn segment_path(output_path: &Path, index: usize) -> PathBuf {
+    let mut name = part_path(output_path).into_os_string();
+    name.push(format!(".{}", index));
+    PathBuf::from(name)
+}
+
+/// Path of the resume state sidecar for an output file
+fn state_path(output_path: &Path) -> PathBuf {
+    let mut name = output_path.as_os_str().to_os_string();
+    name.push(".part.json");
+    PathBuf::from(name)
+}
+
+async fn load_state(path: &Path) -> Option<PartState> {
+    let content = fs::read_to_string(path).await.ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+async fn save_state(path: &Path, state: &PartState) -> Result<()> {
+    let content = serde_json::to_string_pretty(state)
+        .map_err(|e| Error::IoError(format!("Failed to serialize download state: {}", e)))?;
+    fs::write(path, content).await?;
+    Ok(())
+}
+
+/// Parse the total size out of a `Content-Range: bytes start-end/total` header
+fn content_range_total(response: &reqwest::Response) -> Option<(u64, Option<u64>)> {
+    let value = response.headers().get(header::CONTENT_RANGE)?.to_str().ok()?;
+    let range = value.strip_prefix("bytes ")?;
+    let (span, total) = range.split_once('/')?;
+    let start = span.split_once('-')?.0.trim().parse().ok()?;
+    Some((start, total.trim().parse().ok()))
+}
+
+/// Download a URL into `output_path` over up to `connections` parallel connections.
+///
+/// When more than one connection is allowed and the server supports byte ranges, the
+/// file is split into that many ranges which are fetched concurrently and reassembled
+/// in order. Otherwise it is streamed over a single connection. Either way an
+/// interrupted download is resumed on the next call with the same output path.
+pub async fn download_file(
+    client: &Client,
+    url: &str,
+    headers: header::HeaderMap,
+    output_path: &Path,
+    connections: usize,
+    progress_tx: Arc<watch::Sender<f64>>,
+) -> Result<()> {
+    if connections > 1 {
+        match probe_ranges(client, url, &headers).await {
+            Ok(Some(state)) if state.total_size.unwrap_or(0) >= MIN_SEGMENT_SIZE * 2 => {
+                return download_segmented(client, url, headers, output_path, connections, state, progress_tx).await;
+            }
+            Ok(_) => debug!("{} does not support ranged requests, using a single connection", url),
+            Err(e) => debug!("Range probe for {} failed: {}", url, e),
+        }
+    }
+
+    download_single(client, url, headers, output_path, progress_tx).await
+}
+
+/// Check whether the server honours byte ranges for a URL, returning the entity's state if so
+async fn probe_ranges(client: &Client, url: &str, headers: &header::HeaderMap) -> Result<Option<PartState>> {
+    let response = client
+        .get(url)
+        .headers(headers.clone())
+        .header(header::RANGE, "bytes=0-0")
+        .send()
+        .await?;
+
+    let accepts_ranges = response.headers()
+        .get(header::ACCEPT_RANGES)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("bytes"));
+
+    if response.status() != StatusCode::PARTIAL_CONTENT && !accepts_ranges {
+        return Ok(None);
+    }
+
+    let total = content_range_total(&response).and_then(|(_, total)| total);
+    Ok(total.map(|total| PartState::from_response(url, &response, Some(total))))
+}
+
+/// Fetch a file as `connections` concurrent byte ranges.
+///
+/// Each range is written to its own `<output>.part.<n>` file so that an interrupted
+/// download only refetches the missing tail of every range. Once all ranges are complete
+/// they are concatenated in order and moved into place.
+async fn download_segmented(
+    client: &Client,
+    url: &str,
+    headers: header::HeaderMap,
+    output_path: &Path,
+    connections: usize,
+    mut state: PartState,
+    progress_tx: Arc<watch::Sender<f64>>,
+) -> Result<()> {
+    let total = state.total_size.unwrap_or(0);
+    let state_file = state_path(output_path);
+
+    // Keep existing ranges only if they belong to the same entity and split
+    let segments = match load_state(&state_file).await {
+        Some(previous) if previous.url == url && previous.segments > 0 && previous.matches(&state) => {
+            debug!("Resuming segmented download of {}", url);
+            previous.segments
+        }
+        previous => {
+            if let Some(previous) = previous {
+                remove_segments(output_path, previous.segments).await;
+            }
+            let max_segments = (total / MIN_SEGMENT_SIZE).max(1) as usize;
+            connections.min(max_segments)
+        }
+    };
+
+    state.segments = segments;
+    save_state(&state_file, &state).await?;
+
+    let segment_size = total.div_ceil(segments as u64);
+    let ranges: Vec<(u64, u64)> = (0..segments as u64)
+        .map(|i| (i * segment_size, ((i + 1) * segment_size).min(total) - 1))
+        .collect();
+
+    let downloaded = AtomicU64::new(0);
+    let if_range = state.if_range().map(|s| s.to_string());
+
+    let tasks = ranges.iter().enumerate().map(|(index, &(start, end))| {
+        let path = segment_path(output_path, index);
+        let headers = headers.clone();
+        let if_range = if_range.clone();
+        let downloaded = &downloaded;
+        let progress_tx = &progress_tx;
+
+        async move {
+            let length = end - start + 1;
+            let mut existing = fs::metadata(&path).await.map(|m| m.len()).unwrap_or(0);
+            if existing > length {
+                // More bytes than the range holds would corrupt the reassembled file
+                debug!("Range {} of {} is longer than expected, refetching it", index, url);
+                fs::remove_file(&path).await?;
+                existing = 0;
+            }
+            downloaded.fetch_add(existing, Ordering::Relaxed);
+            if existing == length {
+                return Ok::<(), Error>(());
+            }
+
+            let mut if_range = if_range;
+            let response = loop {
+                let mut request = client
+                    .get(url)
+                    .headers(headers.clone())
+                    .header(header::RANGE, format!("bytes={}-{}", start + existing, end));
+                if let Some(if_range) = &if_range {
+                    request = request.header(header::IF_RANGE, if_range.as_str());
+                }
+
+                let response = request.send().await?;
+                match response.status() {
+                    StatusCode::PARTIAL_CONTENT => break response,
+                    // The server didn't accept the validator and sent the whole file; the
+                    // probe already matched the entity, so refetch the range without it
+                    StatusCode::OK if if_range.is_some() => {
+                        debug!("Server ignored If-Range for range {} of {}, restarting it", index, url);
+                        drop(response);
+                        let _ = fs::remove_file(&path).await;
+                        downloaded.fetch_sub(existing, Ordering::Relaxed);
+                        existing = 0;
+                        if_range = None;
+                    }
+                    status => return Err(Error::DownloadFailed {
+                        reason: format!("Server responded with {} to a ranged request for {}", status, url),
+                    }),
+                }
+            };
+
+            let mut file = OpenOptions::new().create(true).append(true).open(&path).await?;
+            let mut stream = response.bytes_stream();
+            while let Some(chunk) = stream.next().await {
+                let chunk = chunk?;
+                file.write_all(&chunk).await?;
+                let done = downloaded.fetch_add(chunk.len() as u64, Ordering::Relaxed) + chunk.len() as u64;
+                let _ = progress_tx.send(done as f64 / total as f64);
+            }
+            file.flush().await?;
+
+            let written = fs::metadata(&path).await?.len();
+            if written < length {
+                return Err(Error::DownloadFailed {
+                    reason: format!("Range {} ended after {} of {} bytes; rerun to resume", index, written, length),
+                });
+            }
+            Ok(())
+        }
+    });
+
+    try_join_all(tasks).await?;
+
+    // Reassemble the ranges in order into the part file
+    let part = part_path(output_path);
+    let mut file = fs::File::create(&part).await?;
+    for index in 0..segments {
+        let mut segment = fs::File::open(segment_path(output_path, index)).await?;
+        tokio::io::copy(&mut segment, &mut file).await?;
+    }
+    file.flush().await?;
+    drop(file);
+
+    remove_segments(output_path, segments).await;
+
+    finish(&part, &state_file, output_path, &progress_tx).await
+}
+
+/// Download a URL over a single connection, resuming an earlier partial download when possible.
+///
+/// Data is written to `<output>.part` with a `<output>.part.json` sidecar recording the
+/// URL and the server's validators. A later call with the same output path sends
+/// `Range: bytes=N-` and only appends when the server answers with `206 Partial Content`
+/// for the same entity; otherwise the partial file is discarded and the download restarts.
+/// The part file is renamed onto `output_path` once the download is complete.
+async fn download_single(
+    client: &Client,
+    url: &str,
+    headers: header::HeaderMap,
+    output_path: &Path,
+    progress_tx: Arc<watch::Sender<f64>>,
+) -> Result<()> {
+    let part = part_path(output_path);
+    let state_file = state_path(output_path);
+
+    // Only resume a partial file that was started from the same URL. Ranges left by a
+    // segmented download can't be continued over a single connection.
+    let previous = match load_state(&state_file).await {
+        Some(state) if state.segments > 0 => {
+            remove_segments(output_path, state.segments).await;
+            None
+        }
+        Some(state) if state.url == url && part.exists() => Some(state),
+        _ => None,
+    };
+    let mut offset = match &previous {
+        Some(_) => fs::metadata(&part).await.map(|m| m.len()).unwrap_or(0),
+        None => 0,
+    };
+
+    let mut request = client
+        .get(url)
+        .headers(headers.clone())
+        .header(header::RANGE, format!("bytes={}-", offset));
+    if let Some(if_range) = previous.as_ref().and_then(|s| s.if_range()) {
+        request = request.header(header::IF_RANGE, if_range);
+    }
+
+    let response = request.send().await?;
+    let status = response.status();
+
+    // The partial file already holds the whole entity
+    if status == StatusCode::RANGE_NOT_SATISFIABLE && offset > 0 {
+        if previous.as_ref().and_then(|s| s.total_size) == Some(offset) {
+            return finish(&part, &state_file, output_path, &progress_tx).await;
+        }
+        debug!("Server rejected resume range, restarting download of {}", url);
+        discard(&part, &state_file).await;
+        return Box::pin(download_single(client, url, headers, output_path, progress_tx)).await;
+    }
+
+    if !status.is_success() {
+        return Err(Error::DownloadFailed {
+            reason: format!("Server responded with {} for {}", status, url),
+        });
+    }
+
+    let (append, total_size) = if status == StatusCode::PARTIAL_CONTENT {
+        let (start, total) = content_range_total(&response)
+            .ok_or_else(|| Error::DownloadFailed { reason: "Invalid Content-Range header".into() })?;
+        (start == offset && offset > 0, total)
+    } else {
+        (false, response.content_length())
+    };
+
+    let state = PartState::from_response(url, &response, total_size);
+    let resumable = append && previous.as_ref().is_some_and(|p| p.matches(&state));
+
+    if resumable {
+        debug!("Resuming {} at byte {}", url, offset);
+    } else if offset > 0 && status == StatusCode::PARTIAL_CONTENT {
+        // The server honoured the range for a different entity; start over from byte 0
+        debug!("Remote file changed, restarting download of {}", url);
+        drop(response);
+        discard(&part, &state_file).await;
+        return Box::pin(download_single(client, url, headers, output_path, progress_tx)).await;
+    } else {
+        offset = 0;
+    }
+
+    save_state(&state_file, &state).await?;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumable)
+        .truncate(!resumable)
+        .open(&part)
+        .await?;
+
+    let mut downloaded = offset;
+    let mut stream = response.bytes_stream();
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        file.write_all(&chunk).await?;
+        downloaded += chunk.len() as u64;
+
+        if let Some(total) = total_size.filter(|t| *t > 0) {
+            let _ = progress_tx.send(downloaded as f64 / total as f64);
+        }
+    }
+
+    file.flush().await?;
+    drop(file);
+
+    if let Some(total) = total_size {
+        if downloaded < total {
+            return Err(Error::DownloadFailed {
+                reason: format!("Connection closed after {} of {} bytes; rerun to resume", downloaded, total),
+            });
+        }
+    }
+
+    finish(&part, &state_file, output_path, &progress_tx).await
+}
+
+/// Move a completed part file into place and drop its sidecar state
+async fn finish(part: &Path, state_file: &Path, output_path: &Path, progress_tx: &watch::Sender<f64>) -> Result<()> {
+    fs::rename(part, output_path).await?;
+    let _ = fs::remove_file(state_file).await;
+    let _ = progress_tx.send(1.0);
+    Ok(())
+}
+
+/// Remove the range files of a segmented download
+async fn remove_segments(output_path: &Path, segments: usize) {
+    for index in 0..segments {
+        let _ = fs::remove_file(segment_path(output_path, index)).await;
+    }
+}
+
+/// Remove a partial download and its sidecar state
+async fn discard(part: &Path, state_file: &Path) {
+    let _ = fs::remove_file(part).await;
+    let _ = fs::remove_file(state_file).await;
+}
@@ -1,31 +1,53 @@
 use async_trait::async_trait;
+use futures_util::future::{self, Future};
+use futures_util::stream::{self, BoxStream, StreamExt};
+use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::sync::Arc;
 use std::fmt;
+use std::str::FromStr;
 use tokio::sync::watch;
 use url::Url;
 
-use crate::Result;
+use crate::{Config, Result};
+use crate::error::platform_err;
 
 pub mod detector;
+pub mod http;
+pub mod hls;
+pub mod dash;
 pub mod youtube;
 pub mod tiktok;
 pub mod reddit;
+pub mod direct;
 
 // Factory for creating platform implementations
 pub struct PlatformFactory;
 
 impl PlatformFactory {
     /// Register all supported platforms
-    pub fn register_platforms(detector: &mut detector::PlatformDetector) {
+    pub fn register_platforms(detector: &mut detector::PlatformDetector, config: &Config) {
+        let youtube = Arc::new(youtube::YouTube::default());
+        let tiktok = Arc::new(tiktok::TikTok::with_config(config));
+        let direct = Arc::new(direct::DirectStream::with_config(config));
+
+        // Reddit link posts hand their media to the platform of the linked site
+        let mut linked = detector::PlatformDetector::empty();
+        linked.register(youtube.clone());
+        linked.register(tiktok.clone());
+        linked.register(direct.clone());
+
         // Register YouTube platform
-        detector.register(Arc::new(youtube::YouTube::default()));
+        detector.register(youtube);
         
         // Register TikTok platform
-        detector.register(Arc::new(tiktok::TikTok::default()));
+        detector.register(tiktok);
 
         // Register Reddit platform
-        detector.register(reddit::RedditPlatform::new());
+        detector.register(Arc::new(reddit::RedditPlatform::with_config(config).with_linked_platforms(linked)));
+
+        // Direct stream URLs are matched last so site-specific platforms take precedence
+        detector.register(direct);
         
         // When adding new platforms, register them here:
         // detector.register(Arc::new(vimeo::Vimeo::default()));
@@ -34,7 +56,7 @@ impl PlatformFactory {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Quality {
     Low,
     Medium,
@@ -59,7 +81,32 @@ impl fmt::Display for Quality {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl Quality {
+    /// Map a video height in pixels to the closest quality tier
+    pub fn from_height(height: u32) -> Self {
+        match height {
+            h if h >= 2160 => Quality::UHD2160,
+            h if h >= 1080 => Quality::HD1080,
+            h if h >= 720 => Quality::HD720,
+            h if h >= 480 => Quality::Medium,
+            _ => Quality::Low,
+        }
+    }
+
+    /// Highest video height in pixels this quality allows, or `None` for no limit
+    pub fn max_height(&self) -> Option<u32> {
+        match self {
+            Quality::Low => Some(360),
+            Quality::Medium => Some(480),
+            Quality::High | Quality::HD720 => Some(720),
+            Quality::HD1080 => Some(1080),
+            Quality::UHD2160 => Some(2160),
+            Quality::Custom(q) => q.trim_end_matches(['p', 'P']).parse().ok(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Format {
     MP4,
     WebM,
@@ -78,21 +125,337 @@ impl fmt::Display for Format {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VideoFormat {
     pub id: String,
     pub quality: Quality,
     pub format: Format,
     pub file_size: Option<u64>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fps: Option<f64>,
+    pub vcodec: Option<String>,
+    pub acodec: Option<String>,
+    /// Video bitrate in kbit/s, or the total bitrate of a muxed stream
+    pub video_bitrate: Option<u32>,
+    /// Audio bitrate in kbit/s
+    pub audio_bitrate: Option<u32>,
+    /// Whether the video uses a high dynamic range transfer (PQ, HLG or Dolby Vision)
+    pub hdr: bool,
+    /// Language of the audio track, e.g. `en`
+    pub language: Option<String>,
+    pub has_video: bool,
+    pub has_audio: bool,
 }
 
-#[derive(Debug, Clone)]
+impl VideoFormat {
+    /// A muxed video and audio format with no stream details; fill in what the platform
+    /// knows with struct update syntax
+    pub fn new(id: impl Into<String>, quality: Quality, format: Format) -> Self {
+        Self {
+            id: id.into(),
+            quality,
+            format,
+            file_size: None,
+            width: None,
+            height: None,
+            fps: None,
+            vcodec: None,
+            acodec: None,
+            video_bitrate: None,
+            audio_bitrate: None,
+            hdr: false,
+            language: None,
+            has_video: true,
+            has_audio: true,
+        }
+    }
+
+    /// Short description of the streams, such as `1920x1080 60fps avc1.640028 + mp4a.40.2`
+    pub fn stream_summary(&self) -> String {
+        let mut parts = Vec::new();
+
+        if self.has_video {
+            match (self.width, self.height) {
+                (Some(w), Some(h)) => parts.push(format!("{}x{}", w, h)),
+                (None, Some(h)) => parts.push(format!("{}p", h)),
+                _ => {}
+            }
+            if let Some(fps) = self.fps {
+                parts.push(format!("{}fps", fps.round()));
+            }
+            if self.hdr {
+                parts.push("HDR".to_string());
+            }
+        }
+
+        let codecs = match (self.has_video, self.has_audio) {
+            (true, true) => format!(
+                "{} + {}",
+                self.vcodec.as_deref().unwrap_or("unknown video"),
+                self.acodec.as_deref().unwrap_or("unknown audio")
+            ),
+            (true, false) => format!("{} (video only)", self.vcodec.as_deref().unwrap_or("unknown video")),
+            (false, _) => format!("{} (audio only)", self.acodec.as_deref().unwrap_or("unknown audio")),
+        };
+        parts.push(codecs);
+
+        if let Some(language) = &self.language {
+            parts.push(format!("[{}]", language));
+        }
+
+        parts.join(" ")
+    }
+}
+
+/// Format a Unix timestamp as a `YYYYMMDD` date in UTC
+pub fn date_from_timestamp(timestamp: i64) -> String {
+    // Days-to-civil conversion from Howard Hinnant's date algorithms
+    let days = timestamp.div_euclid(86_400);
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}{:02}{:02}", year, month, day)
+}
+
+/// Split an RFC 6381 codecs list such as `avc1.64001f,mp4a.40.2` into its video and audio codecs
+pub fn split_codecs(codecs: &str) -> (Option<String>, Option<String>) {
+    const AUDIO_CODECS: [&str; 8] = ["mp4a", "opus", "vorbis", "ac-3", "ec-3", "flac", "mp3", "alac"];
+
+    let mut video = None;
+    let mut audio = None;
+    for codec in codecs.split(',').map(str::trim).filter(|c| !c.is_empty()) {
+        let family = codec.split('.').next().unwrap_or(codec).to_lowercase();
+        let slot = if AUDIO_CODECS.contains(&family.as_str()) { &mut audio } else { &mut video };
+        slot.get_or_insert_with(|| codec.to_string());
+    }
+    (video, audio)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VideoInfo {
+    /// URL the video is downloaded from
     pub url: Url,
+    /// ID of the video on its platform
+    pub id: Option<String>,
+    /// Name of the platform the video was extracted by
+    #[serde(default)]
+    pub platform: String,
+    /// Page the video is shown on, such as the YouTube watch page or the Reddit post
+    pub webpage_url: Option<Url>,
     pub title: String,
     pub description: Option<String>,
     pub duration: Option<u64>,
+    #[serde(default)]
     pub formats: Vec<VideoFormat>,
+    #[serde(default)]
+    pub subtitles: Vec<SubtitleTrack>,
+    /// Preview images, in ascending order of preference
+    #[serde(default)]
+    pub thumbnails: Vec<Thumbnail>,
+    /// Name of the channel or account that published the video
+    pub uploader: Option<String>,
+    /// Handle or channel ID of the uploader
+    pub uploader_id: Option<String>,
+    /// Publication date as `YYYYMMDD`
+    pub upload_date: Option<String>,
+    pub view_count: Option<u64>,
+    pub like_count: Option<u64>,
+    pub comment_count: Option<u64>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    #[serde(default)]
+    pub chapters: Vec<Chapter>,
+    /// Time range the URL points to, such as a start time or a clip
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub section: Option<Section>,
+}
+
+impl VideoInfo {
+    /// The largest thumbnail, or the most preferred one when sizes are unknown
+    pub fn best_thumbnail(&self) -> Option<&Thumbnail> {
+        self.thumbnails.iter()
+            .enumerate()
+            .max_by_key(|(index, thumbnail)| {
+                let area = thumbnail.width.unwrap_or(0) as u64 * thumbnail.height.unwrap_or(0) as u64;
+                (area, *index)
+            })
+            .map(|(_, thumbnail)| thumbnail)
+    }
+}
+
+/// A preview image of a video
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Thumbnail {
+    pub url: Url,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+/// A named part of a video, with times in seconds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Chapter {
+    pub title: String,
+    pub start: f64,
+    pub end: f64,
+}
+
+/// A subtitle or caption track offered for a video
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubtitleTrack {
+    /// Language code, e.g. `en` or `pt-BR`
+    pub language: String,
+    pub name: Option<String>,
+    pub url: Url,
+    /// Subtitle format as served, e.g. `vtt` or `srt`
+    pub ext: String,
+    /// Whether the track was generated by speech recognition or machine translation
+    pub auto_generated: bool,
+}
+
+/// A single item of a playlist, channel, profile or listing
+#[derive(Debug, Clone)]
+pub struct PlaylistEntry {
+    pub url: Url,
+    pub title: Option<String>,
+}
+
+/// Which entries of a playlist, channel, profile or listing to download
+#[derive(Debug, Clone, Default)]
+pub struct PlaylistRange {
+    /// First entry to download (1-based)
+    pub start: Option<usize>,
+    /// Last entry to download (inclusive)
+    pub end: Option<usize>,
+    /// Stop after this many entries, counted from `start`
+    pub max_items: Option<usize>,
+    /// Time window for listings ranked by score, such as `week` (Reddit `top`)
+    pub time: Option<String>,
+}
+
+impl PlaylistRange {
+    /// First and last entry of the range (1-based, inclusive), with `max_items` folded in
+    pub fn bounds(&self) -> (usize, Option<usize>) {
+        let first = self.start.unwrap_or(1).max(1);
+        let last = match (self.end, self.max_items.map(|max| first + max.saturating_sub(1))) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        (first, last)
+    }
+
+    /// Restrict a stream of entries to the selected range without fetching pages past the end
+    pub fn apply<'a>(
+        &self,
+        entries: BoxStream<'a, Result<PlaylistEntry>>,
+    ) -> BoxStream<'a, Result<PlaylistEntry>> {
+        let (first, last) = self.bounds();
+        match last {
+            Some(last) => entries.skip(first - 1).take((last + 1).saturating_sub(first)).boxed(),
+            None => entries.skip(first - 1).boxed(),
+        }
+    }
+}
+
+/// A time range of a video to download, in seconds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Section {
+    pub start: f64,
+    /// `None` downloads to the end of the video
+    pub end: Option<f64>,
+    /// Re-encode so the section starts exactly at `start` instead of at the preceding keyframe
+    pub accurate: bool,
+}
+
+impl Section {
+    pub fn duration(&self) -> Option<f64> {
+        self.end.map(|end| end - self.start)
+    }
+
+    /// The chapters of a video as they fall in this section: trimmed to it and shifted so
+    /// that times count from its start. Chapters outside the section are dropped.
+    pub fn clip_chapters(&self, chapters: &[Chapter]) -> Vec<Chapter> {
+        let end = self.end.unwrap_or(f64::INFINITY);
+        chapters.iter()
+            .filter(|chapter| chapter.end > self.start && chapter.start < end)
+            .map(|chapter| Chapter {
+                title: chapter.title.clone(),
+                start: chapter.start.max(self.start) - self.start,
+                end: chapter.end.min(end) - self.start,
+            })
+            .collect()
+    }
+}
+
+impl FromStr for Section {
+    type Err = crate::Error;
+
+    /// Parse `START-END` with times as seconds, `M:SS` or `H:MM:SS`; either side may be empty
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || crate::Error::InvalidArgument(format!("Invalid section \"{}\", expected START-END such as 1:30-2:00", s));
+
+        let (start, end) = s.trim().trim_start_matches('*').split_once('-').ok_or_else(invalid)?;
+        let start = match start.trim() {
+            "" => 0.0,
+            start => parse_time(start).ok_or_else(invalid)?,
+        };
+        let end = match end.trim() {
+            "" | "inf" => None,
+            end => Some(parse_time(end).ok_or_else(invalid)?),
+        };
+
+        if end.is_some_and(|end| end <= start) {
+            return Err(invalid());
+        }
+        Ok(Section { start, end, accurate: false })
+    }
+}
+
+/// Parse a time given as seconds, `M:SS` or `H:MM:SS`, with optional fractional seconds
+pub fn parse_time(value: &str) -> Option<f64> {
+    let mut seconds = 0.0;
+    for part in value.split(':') {
+        let part: f64 = part.parse().ok()?;
+        if part < 0.0 {
+            return None;
+        }
+        seconds = seconds * 60.0 + part;
+    }
+    Some(seconds)
+}
+
+/// Build a lazy entry stream from a page fetcher.
+///
+/// `fetch` is called with the current cursor and returns the entries of that page
+/// together with the cursor of the next page, or `None` when the listing is exhausted.
+/// Pages are only requested as the stream is polled.
+pub fn paginate<'a, C, F, Fut>(first: C, fetch: F) -> BoxStream<'a, Result<PlaylistEntry>>
+where
+    C: Send + 'a,
+    F: FnMut(C) -> Fut + Send + 'a,
+    Fut: Future<Output = Result<(Vec<PlaylistEntry>, Option<C>)>> + Send + 'a,
+{
+    stream::unfold((Some(first), fetch), |(cursor, mut fetch)| async move {
+        let cursor = cursor?;
+        match fetch(cursor).await {
+            Ok((entries, next)) => Some((Ok(entries), (next, fetch))),
+            Err(e) => Some((Err(e), (None, fetch))),
+        }
+    })
+    .flat_map(|page| match page {
+        Ok(entries) => stream::iter(entries.into_iter().map(Ok)).boxed(),
+        Err(e) => stream::once(future::ready(Err(e))).boxed(),
+    })
+    .boxed()
 }
 
 #[async_trait]
@@ -100,6 +463,32 @@ pub trait Platform: Send + Sync {
     fn name(&self) -> &'static str;
     fn supports_url(&self, url: &Url) -> bool;
     async fn extract_info(&self, url: &Url) -> Result<VideoInfo>;
+
+    /// ID of the video a URL points to, when it can be read from the URL alone.
+    ///
+    /// The download archive uses it to skip known videos without extracting them. It
+    /// should match the `id` that `extract_info` returns; where the page only turns out
+    /// to show another video on extraction, such as a Reddit crosspost or link post, the
+    /// archive records both.
+    fn video_id(&self, _url: &Url) -> Option<String> {
+        None
+    }
+
+    /// Whether the URL points to a collection (playlist, channel, profile or listing)
+    /// rather than a single video
+    fn is_collection(&self, _url: &Url) -> bool {
+        false
+    }
+
+    /// Lazily yield the entries of a collection URL, following pagination as needed.
+    ///
+    /// Only entries within `range` are yielded. Platforms that page through the site
+    /// themselves can restrict their stream with [`PlaylistRange::apply`].
+    fn expand<'a>(&'a self, _url: &'a Url, _range: &'a PlaylistRange) -> BoxStream<'a, Result<PlaylistEntry>> {
+        let err = platform_err(format!("{} does not support playlists", self.name()));
+        stream::once(future::ready(Err(err))).boxed()
+    }
+
     async fn download_video(
         &self,
         info: &VideoInfo,
@@ -107,8 +496,62 @@ pub trait Platform: Send + Sync {
         output_path: &Path,
         progress_tx: Arc<watch::Sender<f64>>,
     ) -> Result<()>;
+
+    /// Download only `section` of a format.
+    ///
+    /// Returns `Ok(false)` without downloading anything when the platform cannot fetch part
+    /// of a video; the caller then cuts the section out of a full download.
+    async fn download_section(
+        &self,
+        _info: &VideoInfo,
+        _format_id: &str,
+        _section: &Section,
+        _output_path: &Path,
+        _progress_tx: Arc<watch::Sender<f64>>,
+    ) -> Result<bool> {
+        Ok(false)
+    }
 }
 
 pub fn normalize_url(url: &str) -> Result<Url> {
     Url::parse(url).map_err(|_| crate::Error::InvalidUrl(url.to_string()))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_section() {
+        let section: Section = "1:30-2:00.5".parse().unwrap();
+        assert_eq!(section, Section { start: 90.0, end: Some(120.5), accurate: false });
+        assert_eq!(section.duration(), Some(30.5));
+
+        assert_eq!("*1:00:00-".parse::<Section>().unwrap().start, 3600.0);
+        assert_eq!("-45".parse::<Section>().unwrap(), Section { start: 0.0, end: Some(45.0), accurate: false });
+        assert!("2:00-1:00".parse::<Section>().is_err());
+        assert!("90".parse::<Section>().is_err());
+        assert!("a-b".parse::<Section>().is_err());
+    }
+
+    #[test]
+    fn test_clip_chapters() {
+        let chapter = |title: &str, start, end| Chapter { title: title.to_string(), start, end };
+        let chapters = [chapter("Intro", 0.0, 60.0), chapter("Main", 60.0, 300.0), chapter("Outro", 300.0, 330.0)];
+
+        let section: Section = "0:30-2:00".parse().unwrap();
+        assert_eq!(section.clip_chapters(&chapters), [chapter("Intro", 0.0, 30.0), chapter("Main", 30.0, 90.0)]);
+
+        let section: Section = "5:10-".parse().unwrap();
+        assert_eq!(section.clip_chapters(&chapters), [chapter("Outro", 0.0, 20.0)]);
+    }
+
+    #[test]
+    fn test_playlist_bounds() {
+        let range = |start, end, max_items| PlaylistRange { start, end, max_items, time: None };
+        assert_eq!(range(None, None, None).bounds(), (1, None));
+        assert_eq!(range(Some(5), Some(20), None).bounds(), (5, Some(20)));
+        assert_eq!(range(Some(5), Some(20), Some(3)).bounds(), (5, Some(7)));
+        assert_eq!(range(None, Some(2), Some(10)).bounds(), (1, Some(2)));
+    }
+}
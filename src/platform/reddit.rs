@@ -3,22 +3,455 @@ use std::sync::Arc;
 use std::path::Path;
 use tokio::sync::watch;
 use url::Url;
-use futures_util::StreamExt;
+use futures_util::stream::BoxStream;
 
-use super::{Format, Platform, Quality, VideoFormat, VideoInfo};
-use crate::{Result, Error};
+use log::{debug, warn};
+use reqwest::{header, Client};
+use serde_json::Value;
 
-#[derive(Default)]
-pub struct RedditPlatform;
+use super::dash::{self, DashDownloader, Manifest, StreamKind};
+use super::hls::HlsDownloader;
+use super::detector::PlatformDetector;
+use super::{date_from_timestamp, http, paginate, Format, Platform, PlaylistEntry, PlaylistRange, Quality, Thumbnail, VideoFormat, VideoInfo};
+use crate::{Config, Result, Error};
+
+const LISTING_PAGE_SIZE: u32 = 100;
+const LISTING_SORTS: [&str; 5] = ["hot", "new", "top", "rising", "controversial"];
+const USER_AGENT: &str = "cli-video-downloader";
+/// Format ID of the video-only `fallback_url` rendition
+const FALLBACK_FORMAT_ID: &str = "fallback";
+
+/// Downloadable media found in a post
+enum PostMedia<'a> {
+    /// A `reddit_video` object with DASH and HLS playlists and a video-only fallback
+    Video(&'a Value),
+    /// Plain MP4 files, such as animated gallery items and GIF previews
+    Files(Vec<MediaFile>),
+    /// A link post whose media is hosted by another supported platform
+    Link(Url),
+}
+
+struct MediaFile {
+    id: String,
+    url: String,
+    height: Option<u32>,
+}
+
+impl MediaFile {
+    fn format(&self) -> VideoFormat {
+        let quality = self.height.map(Quality::from_height).unwrap_or(Quality::Medium);
+
+        // GIF conversions are silent
+        VideoFormat {
+            height: self.height,
+            has_audio: false,
+            ..VideoFormat::new(self.id.clone(), quality, Format::MP4)
+        }
+    }
+}
+
+pub struct RedditPlatform {
+    client: Client,
+    connections: usize,
+    /// Platforms that link posts to other sites are delegated to
+    linked: PlatformDetector,
+}
+
+impl Default for RedditPlatform {
+    fn default() -> Self {
+        Self::with_config(&Config::default())
+    }
+}
 
 impl RedditPlatform {
-    pub fn new() -> Arc<dyn Platform> {
-        Arc::new(Self)
+    /// Create a Reddit platform using the download settings from `config`
+    pub fn with_config(config: &Config) -> Self {
+        Self {
+            client: Client::builder()
+                .user_agent(USER_AGENT)
+                .build()
+                .unwrap_or_default(),
+            connections: config.connections,
+            linked: PlatformDetector::empty(),
+        }
+    }
+
+    /// Delegate posts linking to media on other sites to `linked`
+    pub fn with_linked_platforms(mut self, linked: PlatformDetector) -> Self {
+        self.linked = linked;
+        self
+    }
+
+    fn headers() -> header::HeaderMap {
+        let mut headers = header::HeaderMap::new();
+        headers.insert(header::USER_AGENT, header::HeaderValue::from_static(USER_AGENT));
+        headers
+    }
+
+    /// Whether the URL only identifies a post after following its redirect, as for
+    /// `v.redd.it/<id>` and `/r/<sub>/s/<code>` share links
+    fn needs_redirect(url: &Url) -> bool {
+        let host = url.host_str().unwrap_or("");
+        let segments: Vec<_> = url.path_segments()
+            .map(|s| s.filter(|s| !s.is_empty()).collect())
+            .unwrap_or_default();
+
+        host == "v.redd.it" || segments.get(2) == Some(&"s")
+    }
+
+    /// The post ID of `redd.it/<id>`, `/comments/<id>`, `/gallery/<id>` and `/video/<id>` URLs
+    fn post_id(url: &Url) -> Option<String> {
+        let host = url.host_str().unwrap_or("");
+        let segments: Vec<_> = url.path_segments()
+            .map(|s| s.filter(|s| !s.is_empty()).collect())
+            .unwrap_or_default();
+
+        if host == "redd.it" || host == "www.redd.it" {
+            return segments.first().map(|id| id.to_string());
+        }
+
+        segments.iter()
+            .position(|s| ["comments", "gallery", "video"].contains(s))
+            .and_then(|i| segments.get(i + 1))
+            .map(|id| id.to_string())
+    }
+
+    /// Whether the URL is a `/gallery/<id>` link, which is expanded into its videos
+    fn is_gallery_url(url: &Url) -> bool {
+        url.path_segments().and_then(|mut s| s.next()) == Some("gallery")
+            && Self::gallery_item(url).is_none()
+    }
+
+    /// The gallery item a URL selects with a `#gallery-<n>` fragment, as the format ID of
+    /// that item
+    fn gallery_item(url: &Url) -> Option<&str> {
+        url.fragment().filter(|fragment| fragment.starts_with("gallery-"))
+    }
+
+    /// Playlist entries of a post: one per video of a gallery with several, otherwise the
+    /// post itself
+    fn post_entries(post: &Value) -> Vec<PlaylistEntry> {
+        let Some(permalink) = post["permalink"].as_str() else { return Vec::new() };
+        let Ok(url) = Url::parse(&format!("https://www.reddit.com{}", permalink)) else { return Vec::new() };
+        let title = post["title"].as_str().map(|s| s.to_string());
+
+        let source = match &post["crosspost_parent_list"][0] {
+            parent if parent.is_object() => parent,
+            _ => post,
+        };
+        let files = match source["is_gallery"].as_bool() {
+            Some(true) => Self::gallery_files(source),
+            _ => Vec::new(),
+        };
+        if files.len() < 2 {
+            return vec![PlaylistEntry { url, title }];
+        }
+
+        files.iter()
+            .map(|file| {
+                let mut url = url.clone();
+                url.set_fragment(Some(&file.id));
+                PlaylistEntry { url, title: title.clone() }
+            })
+            .collect()
+    }
+
+    /// Resolve any supported URL form to the JSON endpoint of its post
+    async fn post_json_url(&self, url: &Url) -> Result<Url> {
+        let resolved = if Self::needs_redirect(url) {
+            self.client.get(url.clone()).send().await?.url().clone()
+        } else {
+            url.clone()
+        };
+
+        let id = Self::post_id(&resolved)
+            .ok_or_else(|| Error::InvalidUrl(url.to_string()))?;
+
+        // raw_json keeps media URLs free of HTML entities such as `&amp;`
+        Url::parse(&format!("https://www.reddit.com/comments/{}.json?raw_json=1", id))
+            .map_err(|_| Error::InvalidUrl(url.to_string()))
+    }
+
+    /// Fetch the JSON data of a post
+    async fn fetch_post(&self, url: &Url) -> Result<Value> {
+        let api_url = self.post_json_url(url).await?;
+
+        let data = self.client
+            .get(api_url)
+            .send()
+            .await?
+            .json::<Value>()
+            .await?;
+
+        let post = &data[0]["data"]["children"][0]["data"];
+        if !post.is_object() {
+            return Err(Error::Platform("Could not find post".into()));
+        }
+
+        Ok(post.clone())
+    }
+
+    /// Find the downloadable media of a post, following crossposts to the original
+    fn find_media<'a>(&self, post: &'a Value) -> Result<PostMedia<'a>> {
+        let source = match &post["crosspost_parent_list"][0] {
+            parent if parent.is_object() => parent,
+            _ => post,
+        };
+
+        let reddit_video = [&source["media"]["reddit_video"], &source["secure_media"]["reddit_video"]]
+            .into_iter()
+            .find(|video| video["fallback_url"].is_string());
+        if let Some(video) = reddit_video {
+            return Ok(PostMedia::Video(video));
+        }
+
+        if source["is_gallery"].as_bool().unwrap_or(false) {
+            let files = Self::gallery_files(source);
+            if files.is_empty() {
+                return Err(Error::Platform("Gallery contains no videos or animations".into()));
+            }
+            return Ok(PostMedia::Files(files));
+        }
+
+        let link = Self::external_link(source);
+        if let Some(link) = link.as_ref().filter(|link| self.linked.detect(link).is_ok()) {
+            return Ok(PostMedia::Link(link.clone()));
+        }
+
+        // GIFs and gifv links get a transcoded video preview
+        let preview = &source["preview"]["reddit_video_preview"];
+        if preview["fallback_url"].is_string() {
+            return Ok(PostMedia::Video(preview));
+        }
+
+        let mp4 = &source["preview"]["images"][0]["variants"]["mp4"]["source"];
+        if let Some(url) = mp4["url"].as_str() {
+            return Ok(PostMedia::Files(vec![MediaFile {
+                id: "preview".to_string(),
+                url: url.to_string(),
+                height: mp4["height"].as_u64().map(|h| h as u32),
+            }]));
+        }
+
+        match link {
+            Some(link) => Err(Error::Platform(format!("Post links to unsupported media: {}", link))),
+            None => Err(Error::Platform("Not a video post".into())),
+        }
+    }
+
+    /// The URL of a link post, unless it points back to Reddit itself
+    fn external_link(post: &Value) -> Option<Url> {
+        let url = post["url_overridden_by_dest"].as_str()
+            .or_else(|| post["url"].as_str())
+            .and_then(|u| Url::parse(u).ok())?;
+
+        let host = url.host_str().unwrap_or("");
+        let internal = ["reddit.com", "redd.it"].iter()
+            .any(|domain| host == *domain || host.ends_with(&format!(".{}", domain)));
+
+        (!internal).then_some(url)
+    }
+
+    /// Extract the linked media, keeping the post's title and subreddit
+    async fn extract_link_info(&self, post: &Value, link: &Url) -> Result<VideoInfo> {
+        let platform = self.linked.detect(link)?;
+        let mut linked = platform.extract_info(link).await?;
+
+        let mut description = format!("Posted in r/{}", post["subreddit"].as_str().unwrap_or("unknown"));
+        if let Some(text) = linked.description.take().filter(|d| !d.is_empty()) {
+            description = format!("{}\n\n{}", description, text);
+        }
+
+        // The subreddit is a category like on other posts, next to the linked site's own
+        if let Some(subreddit) = post["subreddit"].as_str() {
+            linked.categories.push(format!("r/{}", subreddit));
+        }
+
+        Ok(VideoInfo {
+            url: link.clone(),
+            title: post["title"].as_str().unwrap_or(&linked.title).to_string(),
+            description: Some(description),
+            ..linked
+        })
+    }
+
+    /// Preview images of a post from smallest to largest, following crossposts
+    fn thumbnails(post: &Value) -> Vec<Thumbnail> {
+        let source = match &post["crosspost_parent_list"][0] {
+            parent if parent.is_object() => parent,
+            _ => post,
+        };
+        let image = &source["preview"]["images"][0];
+
+        let mut thumbnails: Vec<_> = image["resolutions"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .chain([&image["source"]])
+            .filter_map(|t| Some(Thumbnail {
+                url: Url::parse(t["url"].as_str()?).ok()?,
+                width: t["width"].as_u64().map(|w| w as u32),
+                height: t["height"].as_u64().map(|h| h as u32),
+            }))
+            .collect();
+
+        // Posts without a preview only have the small listing thumbnail (or "self"/"default")
+        if thumbnails.is_empty() {
+            if let Some(url) = source["thumbnail"].as_str().and_then(|u| Url::parse(u).ok()) {
+                thumbnails.push(Thumbnail {
+                    url,
+                    width: source["thumbnail_width"].as_u64().map(|w| w as u32),
+                    height: source["thumbnail_height"].as_u64().map(|h| h as u32),
+                });
+            }
+        }
+
+        thumbnails
+    }
+
+    /// The animated items of a gallery, in gallery order
+    fn gallery_files(post: &Value) -> Vec<MediaFile> {
+        post["gallery_data"]["items"]
+            .as_array()
+            .map(|items| {
+                items.iter()
+                    .filter_map(|item| {
+                        let media = &post["media_metadata"][item["media_id"].as_str()?];
+                        let url = media["s"]["mp4"].as_str()?;
+                        Some((url, media["s"]["y"].as_u64()))
+                    })
+                    .enumerate()
+                    .map(|(i, (url, height))| MediaFile {
+                        id: format!("gallery-{}", i + 1),
+                        url: url.to_string(),
+                        height: height.map(|h| h as u32),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Format describing the video-only `fallback_url`, used when no manifest is available
+    fn fallback_format(video: &Value) -> VideoFormat {
+        let height = video["height"].as_u64().map(|h| h as u32);
+        let quality = height.map(Quality::from_height).unwrap_or(Quality::High);
+
+        VideoFormat {
+            width: video["width"].as_u64().map(|w| w as u32),
+            height,
+            video_bitrate: video["bitrate_kbps"].as_u64().map(|b| b as u32),
+            has_audio: false,
+            ..VideoFormat::new(FALLBACK_FORMAT_ID, quality, Format::MP4)
+        }
+    }
+
+    async fn fetch_manifest(&self, video: &Value) -> Result<Manifest> {
+        let dash_url = video["dash_url"]
+            .as_str()
+            .and_then(|u| Url::parse(u).ok())
+            .ok_or_else(|| Error::Platform("Post has no DASH playlist".into()))?;
+
+        DashDownloader::new(&self.client, Self::headers(), self.connections)
+            .fetch_manifest(&dash_url)
+            .await
+    }
+
+    /// Download the DASH renditions selected by `format_id`, muxing in the audio track
+    async fn download_dash(
+        &self,
+        video: &Value,
+        format_id: &str,
+        output_path: &Path,
+        progress_tx: Arc<watch::Sender<f64>>,
+    ) -> Result<()> {
+        let manifest = self.fetch_manifest(video).await?;
+
+        let selected = manifest.select_formats(format_id)?;
+
+        DashDownloader::new(&self.client, Self::headers(), self.connections)
+            .download(&selected, output_path, progress_tx)
+            .await
+    }
+
+    /// Height of the video format requested by `format_id`, if known
+    fn requested_height(info: &VideoInfo, format_id: &str) -> Option<u32> {
+        let id = format_id.split('+').next()?;
+        info.formats.iter()
+            .find(|f| f.id == id)
+            .and_then(|f| f.quality.max_height())
     }
 
     fn is_reddit_url(url: &Url) -> bool {
         let host = url.host_str().unwrap_or("");
-        host == "reddit.com" || host == "www.reddit.com" || host.ends_with(".reddit.com")
+        host == "reddit.com" || host.ends_with(".reddit.com")
+            || ["redd.it", "www.redd.it", "v.redd.it"].contains(&host)
+    }
+
+    /// The canonical path of a subreddit or user listing, such as `/r/videos/top` or
+    /// `/user/someone/submitted`
+    fn listing_path(url: &Url) -> Option<String> {
+        let segments: Vec<_> = url.path_segments()
+            .map(|s| s.filter(|s| !s.is_empty()).collect())
+            .unwrap_or_default();
+
+        match segments.as_slice() {
+            ["r", sub] => Some(format!("/r/{}", sub)),
+            ["r", sub, sort] if LISTING_SORTS.contains(sort) => Some(format!("/r/{}/{}", sub, sort)),
+            ["user" | "u", name] | ["user" | "u", name, "submitted"] => Some(format!("/user/{}/submitted", name)),
+            _ => None,
+        }
+    }
+
+    /// Whether the URL is a subreddit or user listing rather than a single post
+    fn is_listing_url(url: &Url) -> bool {
+        Self::listing_path(url).is_some()
+    }
+
+    /// Fetch one page of a listing, returning its video posts and the `after` cursor of the next page
+    async fn fetch_listing_page(&self, url: &Url, time: Option<&str>, after: String) -> Result<(Vec<PlaylistEntry>, Option<String>)> {
+        let path = Self::listing_path(url).ok_or_else(|| Error::InvalidUrl(url.to_string()))?;
+
+        let mut api_url = Url::parse(&format!("https://www.reddit.com{}.json", path))
+            .map_err(|_| Error::InvalidUrl(url.to_string()))?;
+        {
+            let mut query = api_url.query_pairs_mut();
+            // Keep options such as `sort` from the original URL, replacing the paging ones
+            let replaced: &[&str] = if time.is_some() { &["limit", "after", "raw_json", "t"] } else { &["limit", "after", "raw_json"] };
+            for (key, value) in url.query_pairs() {
+                if !replaced.contains(&key.as_ref()) {
+                    query.append_pair(&key, &value);
+                }
+            }
+            if let Some(time) = time {
+                query.append_pair("t", time);
+            }
+            query.append_pair("limit", &LISTING_PAGE_SIZE.to_string());
+            query.append_pair("raw_json", "1");
+            if !after.is_empty() {
+                query.append_pair("after", &after);
+            }
+        }
+
+        let data = self.client
+            .get(api_url)
+            .send()
+            .await?
+            .json::<Value>()
+            .await?;
+
+        let entries = data["data"]["children"]
+            .as_array()
+            .map(|children| {
+                children.iter()
+                    .map(|child| &child["data"])
+                    .filter(|post| self.find_media(post).is_ok())
+                    .flat_map(Self::post_entries)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        let next = data["data"]["after"].as_str().map(|after| after.to_string());
+        Ok((entries, next))
     }
 }
 
@@ -32,53 +465,115 @@ impl Platform for RedditPlatform {
         Self::is_reddit_url(url)
     }
 
-    async fn extract_info(&self, url: &Url) -> Result<VideoInfo> {
-        // First get the JSON API endpoint for the post
-        let api_url = url.as_str().replace("www.reddit.com", "api.reddit.com");
-        
-        // Fetch post data from Reddit API
-        let client = reqwest::Client::new();
-        let response = client
-            .get(&api_url)
-            .header("User-Agent", "cli-video-downloader")
-            .send()
-            .await?;
+    fn video_id(&self, url: &Url) -> Option<String> {
+        // Share links only reveal the post after a redirect
+        if Self::needs_redirect(url) {
+            return None;
+        }
+        let id = Self::post_id(url)?;
+        Some(match Self::gallery_item(url) {
+            Some(item) => format!("{}-{}", id, item),
+            None => id,
+        })
+    }
 
-        let data = response.json::<serde_json::Value>().await?;
+    fn is_collection(&self, url: &Url) -> bool {
+        Self::is_listing_url(url) || Self::is_gallery_url(url)
+    }
 
-        // Extract video information from the JSON response
-        let post = &data[0]["data"]["children"][0]["data"];
-        
-        // Check if it's a video post
-        if !post["is_video"].as_bool().unwrap_or(false) {
-            return Err(Error::Platform("Not a video post".into()));
+    fn expand<'a>(&'a self, url: &'a Url, range: &'a PlaylistRange) -> BoxStream<'a, Result<PlaylistEntry>> {
+        if Self::is_gallery_url(url) {
+            return range.apply(paginate((), move |()| async move {
+                let post = self.fetch_post(url).await?;
+                Ok((Self::post_entries(&post), None))
+            }));
         }
+        range.apply(paginate(String::new(), move |after| self.fetch_listing_page(url, range.time.as_deref(), after)))
+    }
 
-        // Get video details
-        let video_url = post["media"]["reddit_video"]["fallback_url"]
-            .as_str()
-            .ok_or_else(|| Error::Platform("Could not find video URL".into()))?;
-        
-        let duration = post["media"]["reddit_video"]["duration"]
-            .as_u64()
-            .unwrap_or(0);
-
-        // Create available formats list
-        let formats = vec![
-            VideoFormat {
-                id: "high".to_string(),
-                quality: Quality::High,
-                format: Format::MP4,
-                file_size: None,
+    async fn extract_info(&self, url: &Url) -> Result<VideoInfo> {
+        let post = self.fetch_post(url).await?;
+
+        let (formats, duration) = match self.find_media(&post)? {
+            PostMedia::Video(video) => {
+                // Every rendition in the DASH manifest becomes a format; audio is muxed in on download
+                let formats = match self.fetch_manifest(video).await {
+                    Ok(manifest) => manifest.representations.iter()
+                        .filter(|r| r.kind != StreamKind::Other)
+                        .map(|r| dash::to_video_format(r, manifest.duration))
+                        .collect(),
+                    Err(e) => {
+                        warn!("Could not read the Reddit DASH manifest ({}); only the fallback video without audio is available", e);
+                        vec![Self::fallback_format(video)]
+                    }
+                };
+                (formats, video["duration"].as_u64())
             }
-        ];
+            PostMedia::Files(files) => {
+                let item = Self::gallery_item(url);
+                let formats: Vec<_> = files.iter()
+                    .filter(|file| item.is_none_or(|item| file.id == item))
+                    .map(MediaFile::format)
+                    .collect();
+                if formats.is_empty() {
+                    return Err(Error::Platform(format!("Gallery has no item {}", item.unwrap_or_default())));
+                }
+                if item.is_none() && files.len() > 1 {
+                    warn!(
+                        "{} is a gallery with {} videos and only one is downloaded; use its /gallery/ link to download all of them",
+                        url, files.len()
+                    );
+                }
+                (formats, None)
+            }
+            PostMedia::Link(link) => return self.extract_link_info(&post, &link).await,
+        };
+
+        // Later downloads go straight to the canonical post instead of resolving the URL again
+        let item = Self::gallery_item(url).map(str::to_string);
+        let mut url = post["permalink"].as_str()
+            .and_then(|permalink| Url::parse(&format!("https://www.reddit.com{}", permalink)).ok())
+            .unwrap_or_else(|| url.clone());
+        url.set_fragment(item.as_deref());
+
+        let count = |key: &str| post[key].as_u64();
+
+        // Crossposts share the video of their source, so it is archived under that post
+        let id = post["crosspost_parent_list"][0]["id"].as_str()
+            .or_else(|| post["id"].as_str())
+            .map(|id| match &item {
+                Some(item) => format!("{}-{}", id, item),
+                None => id.to_string(),
+            });
+
+        // Items of a gallery share its title, so they are numbered to keep their files apart
+        let title = post["title"].as_str().unwrap_or("Untitled");
+        let title = match item.as_deref().and_then(|item| item.strip_prefix("gallery-")) {
+            Some(number) => format!("{} ({})", title, number),
+            None => title.to_string(),
+        };
 
         Ok(VideoInfo {
             url: url.clone(),
-            title: post["title"].as_str().unwrap_or("Untitled").to_string(),
+            id,
+            platform: self.name().to_string(),
+            webpage_url: Some(url),
+            title,
             description: Some(post["selftext"].as_str().unwrap_or("").to_string()),
-            duration: Some(duration),
+            duration,
             formats,
+            subtitles: Vec::new(),
+            thumbnails: Self::thumbnails(&post),
+            uploader: post["author"].as_str().map(str::to_string),
+            uploader_id: post["author_fullname"].as_str().map(str::to_string),
+            upload_date: post["created_utc"].as_f64().map(|t| date_from_timestamp(t as i64)),
+            view_count: count("view_count"),
+            like_count: count("ups"),
+            comment_count: count("num_comments"),
+            tags: post["link_flair_text"].as_str().filter(|f| !f.is_empty()).map(|f| vec![f.to_string()]).unwrap_or_default(),
+            categories: post["subreddit"].as_str().map(|s| vec![format!("r/{}", s)]).unwrap_or_default(),
+            chapters: Vec::new(),
+            section: None,
         })
     }
 
@@ -89,50 +584,75 @@ impl Platform for RedditPlatform {
         output_path: &Path,
         progress_tx: Arc<watch::Sender<f64>>,
     ) -> Result<()> {
-        // Get the video URL from the Reddit API again to ensure fresh URL
-        let api_url = info.url.as_str().replace("www.reddit.com", "api.reddit.com");
-        
-        let client = reqwest::Client::new();
-        let mut response = client
-            .get(&api_url)
-            .header("User-Agent", "cli-video-downloader")
-            .send()
-            .await?;
+        // Link posts are downloaded by the linked platform, which extract_info already resolved
+        if !Self::is_reddit_url(&info.url) {
+            let platform = self.linked.detect(&info.url)?;
+            return platform.download_video(info, format_id, output_path, progress_tx).await;
+        }
 
-        let data = response.json::<serde_json::Value>().await?;
-        let post = &data[0]["data"]["children"][0]["data"];
-        
-        let video_url = post["media"]["reddit_video"]["fallback_url"]
-            .as_str()
-            .ok_or_else(|| Error::Platform("Could not find video URL".into()))?;
+        // Fetch the post again to get fresh media URLs
+        let post = self.fetch_post(&info.url).await?;
 
-        // Download the video
-        let response = client
-            .get(video_url)
-            .header("User-Agent", "cli-video-downloader")
-            .send()
-            .await?;
+        let video = match self.find_media(&post)? {
+            PostMedia::Video(video) => video,
+            PostMedia::Files(files) => {
+                let file = if format_id == "best" {
+                    match Self::gallery_item(&info.url) {
+                        Some(item) => files.iter().find(|f| f.id == item),
+                        None => files.first(),
+                    }
+                } else {
+                    files.iter().find(|f| f.id == format_id)
+                };
+                let file = file.ok_or_else(|| Error::InvalidFormat(format_id.to_string()))?;
 
-        let total_size = response.content_length().unwrap_or(0);
-        let mut downloaded: u64 = 0;
-        let mut file = tokio::fs::File::create(output_path).await?;
-
-        let mut stream = response.bytes_stream();
-        use futures_util::StreamExt;
-        while let Some(chunk) = stream.next().await {
-            let chunk = chunk?;
-            tokio::io::AsyncWriteExt::write_all(&mut file, &chunk).await?;
-            downloaded += chunk.len() as u64;
-            
-            if total_size > 0 {
-                let progress = (downloaded as f64 / total_size as f64) * 100.0;
-                if let Err(e) = progress_tx.send(progress) {
-                    return Err(Error::Platform(format!("Failed to send progress update: {}", e)));
-                }
+                return http::download_file(&self.client, &file.url, Self::headers(), output_path, self.connections, progress_tx).await;
+            }
+            PostMedia::Link(link) => {
+                let platform = self.linked.detect(&link)?;
+                let info = platform.extract_info(&link).await?;
+                return platform.download_video(&info, format_id, output_path, progress_tx).await;
             }
+        };
+
+        if format_id == FALLBACK_FORMAT_ID {
+            warn!("Downloading the fallback rendition of {}, which has no audio", info.url);
+        } else {
+            let dash_error = match self.download_dash(video, format_id, output_path, progress_tx.clone()).await {
+                Ok(()) => return Ok(()),
+                Err(Error::InvalidFormat(id)) => return Err(Error::InvalidFormat(id)),
+                Err(e) => e,
+            };
+            debug!("DASH download failed, trying HLS: {}", dash_error);
+
+            let hls_error = match video["hls_url"].as_str().and_then(|u| Url::parse(u).ok()) {
+                Some(hls_url) => {
+                    let quality = Self::requested_height(info, format_id).map(Quality::from_height);
+                    let result = HlsDownloader::new(&self.client, Self::headers(), self.connections)
+                        .download(&hls_url, quality.as_ref(), output_path, progress_tx.clone())
+                        .await;
+                    match result {
+                        Ok(()) => return Ok(()),
+                        Err(e) => e.to_string(),
+                    }
+                }
+                None => "post has no HLS playlist".to_string(),
+            };
+
+            // The fallback has no audio, so it is only used when asked for by name
+            return Err(Error::DownloadFailed {
+                reason: format!(
+                    "Could not fetch the video with its audio (DASH: {}; HLS: {}). Use --select {} to download the video without audio",
+                    dash_error, hls_error, FALLBACK_FORMAT_ID
+                ),
+            });
         }
 
-        Ok(())
+        let video_url = video["fallback_url"]
+            .as_str()
+            .ok_or_else(|| Error::Platform("Could not find video URL".into()))?;
+
+        http::download_file(&self.client, video_url, Self::headers(), output_path, self.connections, progress_tx).await
     }
 }
 
@@ -146,9 +666,12 @@ mod tests {
             "https://reddit.com/r/videos/comments/abc123",
             "https://www.reddit.com/r/funny/comments/def456",
             "https://old.reddit.com/r/gifs/comments/ghi789",
+            "https://redd.it/abc123",
+            "https://v.redd.it/xyz789abc",
         ];
 
         let invalid_urls = [
+            "https://i.redd.it/abc123.gif",
             "https://youtube.com/watch?v=abc123",
             "https://vimeo.com/123456",
             "https://notreddit.com/video",
@@ -164,4 +687,110 @@ mod tests {
             assert!(!RedditPlatform::is_reddit_url(&parsed_url));
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_reddit_listing_detection() {
+        let listings = [
+            "https://www.reddit.com/r/videos",
+            "https://www.reddit.com/r/videos/",
+            "https://old.reddit.com/r/videos/top?t=week",
+            "https://www.reddit.com/user/someone/submitted/",
+            "https://www.reddit.com/u/someone",
+        ];
+
+        let posts = [
+            "https://www.reddit.com/r/videos/comments/abc123/some_title/",
+            "https://www.reddit.com/r/videos/wiki/index",
+            "https://www.reddit.com/user/someone/comments/",
+        ];
+
+        for url in listings.iter() {
+            assert!(RedditPlatform::is_listing_url(&Url::parse(url).unwrap()));
+        }
+
+        for url in posts.iter() {
+            assert!(!RedditPlatform::is_listing_url(&Url::parse(url).unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_reddit_post_resolution() {
+        let posts = [
+            ("https://old.reddit.com/r/gifs/comments/ghi789/title/", "ghi789"),
+            ("https://redd.it/abc123", "abc123"),
+            ("https://www.reddit.com/gallery/def456", "def456"),
+        ];
+
+        for (url, id) in posts.iter() {
+            let url = Url::parse(url).unwrap();
+            assert!(!RedditPlatform::needs_redirect(&url));
+            assert_eq!(RedditPlatform::post_id(&url).as_deref(), Some(*id));
+        }
+
+        for url in ["https://v.redd.it/xyz789abc", "https://www.reddit.com/r/videos/s/AbCdEf"] {
+            assert!(RedditPlatform::needs_redirect(&Url::parse(url).unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_crosspost_gallery_media() {
+        let crosspost = serde_json::json!({
+            "crosspost_parent_list": [{
+                "is_gallery": true,
+                "gallery_data": {"items": [{"media_id": "a"}, {"media_id": "b"}]},
+                "media_metadata": {
+                    "a": {"e": "Image", "s": {"u": "https://i.redd.it/a.jpg", "y": 720}},
+                    "b": {"e": "AnimatedImage", "s": {"mp4": "https://i.redd.it/b.mp4", "y": 480}}
+                }
+            }]
+        });
+
+        match RedditPlatform::default().find_media(&crosspost).unwrap() {
+            PostMedia::Files(files) => {
+                assert_eq!(files.len(), 1);
+                assert_eq!(files[0].id, "gallery-1");
+                assert_eq!(files[0].url, "https://i.redd.it/b.mp4");
+            }
+            _ => panic!("expected gallery files"),
+        }
+    }
+
+    #[test]
+    fn test_gallery_entries() {
+        let gallery = Url::parse("https://www.reddit.com/gallery/abc123").unwrap();
+        assert!(RedditPlatform::is_gallery_url(&gallery));
+        assert!(!RedditPlatform::is_gallery_url(&Url::parse("https://www.reddit.com/gallery/abc123#gallery-2").unwrap()));
+
+        let post = serde_json::json!({
+            "permalink": "/r/gifs/comments/abc123/two_clips/",
+            "title": "Two clips",
+            "is_gallery": true,
+            "gallery_data": {"items": [{"media_id": "a"}, {"media_id": "b"}]},
+            "media_metadata": {
+                "a": {"e": "AnimatedImage", "s": {"mp4": "https://i.redd.it/a.mp4", "y": 720}},
+                "b": {"e": "AnimatedImage", "s": {"mp4": "https://i.redd.it/b.mp4", "y": 480}}
+            }
+        });
+
+        let urls: Vec<_> = RedditPlatform::post_entries(&post).into_iter().map(|e| e.url.to_string()).collect();
+        assert_eq!(urls, [
+            "https://www.reddit.com/r/gifs/comments/abc123/two_clips/#gallery-1",
+            "https://www.reddit.com/r/gifs/comments/abc123/two_clips/#gallery-2",
+        ]);
+
+        let item = Url::parse(&urls[1]).unwrap();
+        assert_eq!(RedditPlatform::default().video_id(&item).as_deref(), Some("abc123-gallery-2"));
+    }
+
+    #[test]
+    fn test_external_link() {
+        let youtube = serde_json::json!({"url_overridden_by_dest": "https://youtu.be/abc123", "url": "https://youtu.be/abc123"});
+        let image = serde_json::json!({"url": "https://i.redd.it/abc123.jpg"});
+
+        assert_eq!(
+            RedditPlatform::external_link(&youtube).map(String::from),
+            Some("https://youtu.be/abc123".to_string())
+        );
+        assert!(RedditPlatform::external_link(&image).is_none());
+    }
+}
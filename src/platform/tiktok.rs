@@ -2,25 +2,26 @@ use async_trait::async_trait;
 use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::watch;
-use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
 use tokio::time::{sleep, Duration};
 use url::Url;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use reqwest::{Client, header, redirect};
-use futures_util::StreamExt;
+use futures_util::stream::BoxStream;
 use regex::Regex;
 use log::{debug, info};
 use std::sync::OnceLock;
 use base64::Engine;
 
-use crate::{Error, Result};
-use super::{Platform, VideoFormat, VideoInfo, Quality, Format};
+use crate::{Config, Error, Result};
+use super::{date_from_timestamp, http, paginate, Platform, PlaylistEntry, PlaylistRange, SubtitleTrack, Thumbnail, VideoFormat, VideoInfo, Quality, Format};
 
 static USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/122.0.0.0 Safari/537.36";
 static CLIENT: OnceLock<Client> = OnceLock::new();
+/// The video ID in the path of a video URL
+static VIDEO_PATH: OnceLock<Regex> = OnceLock::new();
 const DELAY_MS: u64 = 200;
+const PROFILE_PAGE_SIZE: u32 = 30;
 
 fn extract_json_from_html(html: &str) -> Option<Value> {
     let patterns = [
@@ -33,7 +34,7 @@ fn extract_json_from_html(html: &str) -> Option<Value> {
     ];
 
     for pattern in &patterns {
-        if let Some(re) = Regex::new(pattern).ok() {
+        if let Ok(re) = Regex::new(pattern) {
             if let Some(caps) = re.captures(html) {
                 if let Some(json_str) = caps.get(1) {
                     let json_text = json_str.as_str();
@@ -66,7 +67,7 @@ fn extract_json_from_html(html: &str) -> Option<Value> {
     ];
 
     for pattern in &item_patterns {
-        if let Some(re) = Regex::new(pattern).ok() {
+        if let Ok(re) = Regex::new(pattern) {
             if let Some(caps) = re.captures(html) {
                 if let Some(json_str) = caps.get(1) {
                     if let Ok(json) = serde_json::from_str(json_str.as_str()) {
@@ -82,16 +83,31 @@ fn extract_json_from_html(html: &str) -> Option<Value> {
 
 #[derive(Debug, Deserialize, Serialize)]
 struct TikTokVideo {
+    #[serde(default)]
+    id: String,
     desc: String,
+    /// Unix timestamp, serialized as a string by the web app
+    #[serde(rename = "createTime", default)]
+    create_time: Value,
     #[serde(default)]
     video: TikTokVideoDetails,
     author: TikTokAuthor,
     #[serde(rename = "stats")]
     statistics: TikTokStats,
+    /// Hashtags used in the caption
+    #[serde(default)]
+    challenges: Vec<TikTokChallenge>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct TikTokChallenge {
+    title: String,
 }
 
 #[derive(Debug, Deserialize, Serialize, Default)]
 struct TikTokVideoDetails {
+    #[serde(default)]
+    width: u32,
     #[serde(default)]
     height: u32,
     #[serde(default)]
@@ -104,6 +120,27 @@ struct TikTokVideoDetails {
     format: String,
     #[serde(rename = "bitrate", default)]
     bit_rate: u64,
+    #[serde(rename = "codecType", default)]
+    codec_type: String,
+    #[serde(rename = "subtitleInfos", default)]
+    subtitles: Vec<TikTokSubtitle>,
+    #[serde(default)]
+    cover: String,
+    #[serde(rename = "originCover", default)]
+    origin_cover: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct TikTokSubtitle {
+    #[serde(rename = "LanguageCodeName")]
+    language: String,
+    #[serde(rename = "Url")]
+    url: String,
+    #[serde(rename = "Format", default)]
+    format: String,
+    /// `ASR` for speech recognition and `MT` for machine translation
+    #[serde(rename = "Source", default)]
+    source: String,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -116,13 +153,16 @@ struct TikTokAuthor {
 #[derive(Debug, Deserialize, Serialize)]
 struct TikTokStats {
     #[serde(rename = "playCount", default)]
-    play_count: u64,
-    #[serde(rename = "likeCount", default)]
-    like_count: u64,
+    play_count: Option<u64>,
+    #[serde(rename = "diggCount", alias = "likeCount", default)]
+    like_count: Option<u64>,
+    #[serde(rename = "commentCount", default)]
+    comment_count: Option<u64>,
 }
 
 pub struct TikTok {
     client: Client,
+    connections: usize,
 }
 
 impl Default for TikTok {
@@ -133,7 +173,13 @@ impl Default for TikTok {
 
 impl TikTok {
     pub fn new() -> Self {
+        Self::with_config(&Config::default())
+    }
+
+    /// Create a TikTok platform using the download settings from `config`
+    pub fn with_config(config: &Config) -> Self {
         Self {
+            connections: config.connections,
             client: CLIENT.get_or_init(|| {
                 let mut headers = header::HeaderMap::new();
                 headers.insert(header::USER_AGENT, header::HeaderValue::from_static(USER_AGENT));
@@ -280,12 +326,17 @@ impl TikTok {
         }
         
         let video_details = TikTokVideoDetails {
+            width: 0,
             height: 720,
             duration: 30,
             play_url: video_url.clone(),
             download_url: video_url,
             format: "mp4".to_string(),
             bit_rate: 1_000_000,
+            codec_type: String::new(),
+            subtitles: Vec::new(),
+            cover: String::new(),
+            origin_cover: String::new(),
         };
         
         let author = TikTokAuthor {
@@ -294,15 +345,19 @@ impl TikTok {
         };
         
         let stats = TikTokStats {
-            play_count: 0,
-            like_count: 0,
+            play_count: None,
+            like_count: None,
+            comment_count: None,
         };
         
         Ok(TikTokVideo {
+            id: video_id.to_string(),
             desc: video_title,
+            create_time: Value::Null,
             video: video_details,
             author,
             statistics: stats,
+            challenges: Vec::new(),
         })
     }
 
@@ -319,10 +374,10 @@ impl TikTok {
             .header("Upgrade-Insecure-Requests", "1")
             .send()
             .await
-            .map_err(|e| Error::Network(e))?
+            .map_err(Error::Network)?
             .text()
             .await
-            .map_err(|e| Error::Network(e))?;
+            .map_err(Error::Network)?;
 
         // Support more URL patterns: standard format, short format, and mobile format
         let patterns = [
@@ -342,12 +397,11 @@ impl TikTok {
                         username = caps.get(1).unwrap().as_str().to_string();
                         video_id = caps.get(2).unwrap().as_str().to_string();
                     }
-                } else if pattern == &"tiktok\\.com/v/(\\d+)" || pattern == &"/video/(\\d+)" {
-                    if caps.len() > 1 {
+                } else if (pattern == &"tiktok\\.com/v/(\\d+)" || pattern == &"/video/(\\d+)")
+                    && caps.len() > 1 {
                         // For formats without username, we'll use empty string
                         video_id = caps.get(1).unwrap().as_str().to_string();
                     }
-                }
                 
                 if !video_id.is_empty() {
                     break;
@@ -383,7 +437,7 @@ impl TikTok {
         match api_response {
             Ok(response) => {
                 if response.status().is_success() {
-                    let text = response.text().await.map_err(|e| Error::Network(e))?;
+                    let text = response.text().await.map_err(Error::Network)?;
                     
                     if let Ok(api_data) = serde_json::from_str::<Value>(&text) {
                         if let Some(video_data) = api_data.get("aweme_list").and_then(|list| list.get(0)) {
@@ -402,6 +456,88 @@ impl TikTok {
         self.extract_video_url_from_html(&html_response, &video_id).await
     }
 
+    /// Extract the username from a profile URL such as `https://www.tiktok.com/@user`
+    fn profile_username(url: &Url) -> Option<String> {
+        url.path_segments()?
+            .next()
+            .and_then(|segment| segment.strip_prefix('@'))
+            .filter(|name| !name.is_empty())
+            .map(|name| name.to_string())
+    }
+
+    /// Look up the `secUid` TikTok uses to page through a user's posts
+    async fn fetch_sec_uid(&self, username: &str) -> Result<String> {
+        let profile_url = format!("https://www.tiktok.com/@{}", username);
+        sleep(Duration::from_millis(DELAY_MS)).await;
+
+        let html = self.client
+            .get(&profile_url)
+            .header("Accept", "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8")
+            .send()
+            .await?
+            .text()
+            .await?;
+
+        let re = Regex::new(r#""secUid"\s*:\s*"([^"]+)""#).unwrap();
+        re.captures(&html)
+            .and_then(|caps| caps.get(1))
+            .map(|m| m.as_str().to_string())
+            .ok_or_else(|| Error::Platform(format!("Could not find profile data for @{}", username)))
+    }
+
+    /// Fetch one page of a user's posts, returning the entries and the next cursor
+    async fn fetch_profile_page(
+        &self,
+        username: &str,
+        sec_uid: &str,
+        cursor: &str,
+    ) -> Result<(Vec<PlaylistEntry>, Option<String>)> {
+        let api_url = format!(
+            "https://www.tiktok.com/api/post/item_list/?aid=1988&count={}&cursor={}&secUid={}",
+            PROFILE_PAGE_SIZE, cursor, sec_uid
+        );
+        debug!("Fetching profile page: {}", api_url);
+        sleep(Duration::from_millis(DELAY_MS)).await;
+
+        let data = self.client
+            .get(&api_url)
+            .header("Referer", format!("https://www.tiktok.com/@{}", username))
+            .send()
+            .await?
+            .json::<Value>()
+            .await?;
+
+        let entries = data["itemList"]
+            .as_array()
+            .map(|items| {
+                items.iter()
+                    .filter_map(|item| {
+                        let id = item["id"].as_str()?;
+                        let url = Url::parse(&format!("https://www.tiktok.com/@{}/video/{}", username, id)).ok()?;
+                        Some(PlaylistEntry {
+                            url,
+                            title: item["desc"].as_str().map(|s| s.to_string()),
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        // TikTok returns the cursor either as a string or a number
+        let next_cursor = match &data["cursor"] {
+            Value::String(s) => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        };
+
+        let has_more = data["hasMore"].as_bool().unwrap_or(false);
+        if has_more && !entries.is_empty() {
+            Ok((entries, next_cursor))
+        } else {
+            Ok((entries, None))
+        }
+    }
+
     fn determine_quality(&self, height: u32) -> Quality {
         if height >= 1080 {
             Quality::HD1080
@@ -420,39 +556,10 @@ impl TikTok {
         output_path: &Path,
         progress_tx: Arc<watch::Sender<f64>>,
     ) -> Result<()> {
-        let response = self.client
-            .get(url)
-            .header("Range", "bytes=0-")
-            .header("Referer", "https://www.tiktok.com/")
-            .send()
-            .await
-            .map_err(|e| Error::Network(e))?;
-
-        let total_size = response
-            .content_length()
-            .ok_or_else(|| Error::DownloadFailed {
-                reason: "Content length not available".to_string(),
-            })?;
-
-        let mut file = File::create(output_path)
-            .await
-            .map_err(|e| Error::IO(e))?;
-
-        let mut downloaded: u64 = 0;
-        let mut stream = response.bytes_stream();
+        let mut headers = header::HeaderMap::new();
+        headers.insert(header::REFERER, header::HeaderValue::from_static("https://www.tiktok.com/"));
 
-        while let Some(item) = stream.next().await {
-            let chunk = item.map_err(|e| Error::Network(e))?;
-            file.write_all(&chunk)
-                .await
-                .map_err(|e| Error::IO(e))?;
-
-            downloaded += chunk.len() as u64;
-            let progress = (downloaded as f64) / (total_size as f64);
-            let _ = progress_tx.send(progress);
-        }
-
-        Ok(())
+        http::download_file(&self.client, url, headers, output_path, self.connections, progress_tx).await
     }
 }
 
@@ -468,32 +575,101 @@ impl Platform for TikTok {
             .unwrap_or(false)
     }
 
+    fn video_id(&self, url: &Url) -> Option<String> {
+        // Short links only reveal the video after a redirect
+        let re = VIDEO_PATH.get_or_init(|| Regex::new(r"/(?:video|v)/(\d+)").unwrap());
+        re.captures(url.path()).map(|caps| caps[1].to_string())
+    }
+
+    fn is_collection(&self, url: &Url) -> bool {
+        Self::profile_username(url).is_some() && !url.path().contains("/video/")
+    }
+
+    fn expand<'a>(&'a self, url: &'a Url, range: &'a PlaylistRange) -> BoxStream<'a, Result<PlaylistEntry>> {
+        let username = Self::profile_username(url).unwrap_or_default();
+
+        // The cursor carries the lazily resolved secUid alongside TikTok's page cursor
+        let entries = paginate((None::<String>, "0".to_string()), move |(sec_uid, cursor)| {
+            let username = username.clone();
+            async move {
+                let sec_uid = match sec_uid {
+                    Some(sec_uid) => sec_uid,
+                    None => self.fetch_sec_uid(&username).await?,
+                };
+                let (entries, next) = self.fetch_profile_page(&username, &sec_uid, &cursor).await?;
+                Ok((entries, next.map(|cursor| (Some(sec_uid), cursor))))
+            }
+        });
+        range.apply(entries)
+    }
+
     async fn extract_info(&self, url: &Url) -> Result<VideoInfo> {
         let video_info = self.fetch_video_info(url.as_str()).await?;
         let quality = self.determine_quality(video_info.video.height);
 
-        let mut formats = Vec::new();
-        formats.push(VideoFormat {
-            id: "default".to_string(),
-            quality,
-            format: Format::MP4,
-            file_size: Some((video_info.video.bit_rate / 8) * video_info.video.duration),
-        });
+        let video = &video_info.video;
+        let formats = vec![VideoFormat {
+            file_size: Some((video.bit_rate / 8) * video.duration),
+            width: Some(video.width).filter(|w| *w > 0),
+            height: Some(video.height).filter(|h| *h > 0),
+            vcodec: Some(video.codec_type.clone()).filter(|c| !c.is_empty()),
+            video_bitrate: Some((video.bit_rate / 1000) as u32).filter(|b| *b > 0),
+            ..VideoFormat::new("default", quality, Format::MP4)
+        }];
+
+        let subtitles = video.subtitles.iter()
+            .filter_map(|subtitle| Some(SubtitleTrack {
+                language: subtitle.language.clone(),
+                name: None,
+                url: Url::parse(&subtitle.url).ok()?,
+                ext: match subtitle.format.as_str() {
+                    "webvtt" | "" => "vtt".to_string(),
+                    other => other.to_string(),
+                },
+                auto_generated: matches!(subtitle.source.as_str(), "ASR" | "MT"),
+            }))
+            .collect();
+
+        // The cropped cover first, then the full-size original frame
+        let thumbnails = [&video.cover, &video.origin_cover].into_iter()
+            .filter_map(|cover| Url::parse(cover).ok())
+            .map(|url| Thumbnail { url, width: None, height: None })
+            .collect();
+
+        let upload_date = match &video_info.create_time {
+            Value::String(s) => s.parse::<i64>().ok(),
+            value => value.as_i64(),
+        }
+        .filter(|t| *t > 0)
+        .map(date_from_timestamp);
 
-        let desc = format!(
-            "By {} (@{}) - {} plays, {} likes",
-            video_info.author.nickname,
-            video_info.author.unique_id,
-            video_info.statistics.play_count,
-            video_info.statistics.like_count
-        );
+        let author = &video_info.author;
+        let id = Some(video_info.id.clone()).filter(|id| !id.is_empty());
+        let webpage_url = id.as_ref()
+            .filter(|_| !author.unique_id.is_empty())
+            .and_then(|id| Url::parse(&format!("https://www.tiktok.com/@{}/video/{}", author.unique_id, id)).ok());
 
         Ok(VideoInfo {
             url: url.clone(),
-            title: video_info.desc,
-            description: Some(desc),
+            id,
+            platform: self.name().to_string(),
+            webpage_url,
+            title: video_info.desc.clone(),
+            description: Some(video_info.desc.clone()).filter(|d| !d.is_empty()),
             duration: Some(video_info.video.duration),
             formats,
+            subtitles,
+            thumbnails,
+            uploader: Some(author.nickname.clone()).filter(|n| !n.is_empty()),
+            uploader_id: Some(author.unique_id.clone()).filter(|id| !id.is_empty()),
+            upload_date,
+            view_count: video_info.statistics.play_count,
+            like_count: video_info.statistics.like_count,
+            comment_count: video_info.statistics.comment_count,
+            tags: video_info.challenges.iter().map(|c| c.title.clone()).collect(),
+            categories: Vec::new(),
+            chapters: Vec::new(),
+            section: None,
         })
     }
 
@@ -1,180 +1,287 @@
 use async_trait::async_trait;
+use std::collections::BTreeMap;
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use std::process::Stdio;
 use tokio::sync::watch;
 use tokio::process::Command;
-use tokio::io::{BufReader, AsyncBufReadExt};
+use tokio::io::{BufReader, AsyncBufReadExt, AsyncReadExt};
 use url::Url;
 use regex::Regex;
 use serde::Deserialize;
+use futures_util::future;
+use futures_util::stream::{self, BoxStream, StreamExt};
 
 use crate::{Error, Result};
-use super::{Platform, VideoFormat, VideoInfo, Quality, Format};
+use crate::error::command_err;
+use super::{parse_time, Platform, PlaylistEntry, PlaylistRange, Section, Chapter, SubtitleTrack, Thumbnail, VideoFormat, VideoInfo, Quality, Format};
+
+/// `1h2m3s`-style URL times
+static URL_TIME: OnceLock<Regex> = OnceLock::new();
 
 /// YouTube-specific video metadata
 #[derive(Debug, Deserialize)]
 struct YouTubeMetadata {
+    #[serde(default)]
+    id: String,
     title: String,
     description: Option<String>,
     duration: Option<f64>,
-    #[serde(rename = "id")]
-    video_id: String,
+    #[serde(default)]
+    formats: Vec<YtDlpFormat>,
+    #[serde(default)]
+    subtitles: BTreeMap<String, Vec<YtDlpSubtitle>>,
+    #[serde(default)]
+    automatic_captions: BTreeMap<String, Vec<YtDlpSubtitle>>,
+    #[serde(default)]
+    thumbnails: Vec<YtDlpThumbnail>,
+    webpage_url: Option<String>,
+    uploader: Option<String>,
+    /// The `@handle`, falling back to `channel_id` for channels without one
+    uploader_id: Option<String>,
+    channel_id: Option<String>,
+    upload_date: Option<String>,
+    view_count: Option<u64>,
+    like_count: Option<u64>,
+    comment_count: Option<u64>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    categories: Vec<String>,
+    /// Absent or `null` when the video has no chapters
+    chapters: Option<Vec<YtDlpChapter>>,
+    /// Time range of a clip, in seconds
+    section_start: Option<f64>,
+    section_end: Option<f64>,
+}
+
+/// What a YouTube video URL refers to
+#[derive(Debug, PartialEq)]
+enum VideoTarget {
+    /// A video, with the start and end times given in the URL
+    Video { id: String, start: Option<f64>, end: Option<f64> },
+    /// A clip, whose video and time range have to be looked up
+    Clip(Url),
+}
+
+/// A chapter in yt-dlp's JSON output
+#[derive(Debug, Deserialize)]
+struct YtDlpChapter {
+    title: String,
+    start_time: f64,
+    end_time: f64,
+}
+
+/// A thumbnail in yt-dlp's JSON output; the list is sorted by preference, best last
+#[derive(Debug, Deserialize)]
+struct YtDlpThumbnail {
+    url: String,
+    width: Option<u32>,
+    height: Option<u32>,
+}
+
+/// One downloadable rendition of a subtitle track in yt-dlp's JSON output
+#[derive(Debug, Deserialize)]
+struct YtDlpSubtitle {
+    ext: String,
+    url: String,
+    name: Option<String>,
+}
+
+/// One entry of the `formats` array in yt-dlp's JSON output
+#[derive(Debug, Deserialize)]
+struct YtDlpFormat {
+    format_id: String,
+    ext: String,
+    width: Option<u32>,
+    height: Option<u32>,
+    fps: Option<f64>,
+    vcodec: Option<String>,
+    acodec: Option<String>,
+    tbr: Option<f64>,
+    vbr: Option<f64>,
+    abr: Option<f64>,
+    filesize: Option<u64>,
+    filesize_approx: Option<u64>,
+    dynamic_range: Option<String>,
+    language: Option<String>,
+}
+
+impl YtDlpFormat {
+    fn has_video(&self) -> bool {
+        self.vcodec.as_deref().is_some_and(|codec| codec != "none")
+    }
+
+    fn has_audio(&self) -> bool {
+        self.acodec.as_deref().is_some_and(|codec| codec != "none")
+    }
+
+    fn to_video_format(&self) -> VideoFormat {
+        let quality = match (self.has_video(), self.height) {
+            (true, Some(height)) => Quality::from_height(height),
+            (true, None) => Quality::Medium,
+            (false, _) => Quality::Custom(format!("audio {}k", self.abr.or(self.tbr).unwrap_or(0.0).round() as u64)),
+        };
+
+        let format = match self.ext.as_str() {
+            "mp4" => Format::MP4,
+            "webm" => Format::WebM,
+            "mov" => Format::MOV,
+            ext => Format::Other(ext.to_string()),
+        };
+
+        let has_video = self.has_video();
+        let has_audio = self.has_audio();
+        let kbps = |rate: Option<f64>| rate.map(|r| r.round() as u32);
+
+        VideoFormat {
+            file_size: self.filesize.or(self.filesize_approx),
+            width: self.width.filter(|_| has_video),
+            height: self.height.filter(|_| has_video),
+            fps: self.fps.filter(|_| has_video),
+            vcodec: self.vcodec.clone().filter(|_| has_video),
+            acodec: self.acodec.clone().filter(|_| has_audio),
+            video_bitrate: if has_video { kbps(self.vbr.or(self.tbr)) } else { None },
+            audio_bitrate: if has_audio { kbps(self.abr.or(if has_video { None } else { self.tbr })) } else { None },
+            hdr: self.dynamic_range.as_deref().is_some_and(|range| range != "SDR"),
+            language: self.language.clone(),
+            has_video,
+            has_audio,
+            ..VideoFormat::new(self.format_id.clone(), quality, format)
+        }
+    }
 }
 
 #[derive(Default)]
 pub struct YouTube {}
 
 impl YouTube {
-    /// Extract YouTube video ID from a URL
-    async fn extract_video_id(&self, url: &Url) -> Result<String> {
-        // Handle youtu.be short links
-        if url.host_str() == Some("youtu.be") {
-            return url.path_segments()
-                .and_then(|segments| segments.last())
-                .map(|s| s.to_string())
-                .ok_or_else(|| Error::InvalidUrl("Invalid YouTube short URL".into()));
-        }
+    /// Work out which video a URL refers to.
+    ///
+    /// Accepts `youtu.be/<id>` and `/watch?v=<id>`, `/shorts/`, `/embed/`, `/live/`, `/v/`,
+    /// `/e/` and `/clip/` paths on youtube.com, its `www.`, `m.` and `music.` subdomains and
+    /// youtube-nocookie.com. Start and end times come from `t`, `start` and `end` in the
+    /// query or a `#t=` fragment.
+    fn parse_video_url(url: &Url) -> Result<VideoTarget> {
+        let invalid = || Error::InvalidUrl(format!("No video ID found in URL: {}", url));
+        let host = url.host_str().ok_or_else(invalid)?.trim_start_matches("www.");
+        let segments: Vec<_> = url.path_segments().map(|s| s.filter(|s| !s.is_empty()).collect()).unwrap_or_default();
 
-        // Handle standard youtube.com links
-        if let Some(host) = url.host_str() {
-            if host.ends_with("youtube.com") {
-                // Check for /watch?v= format
-                if url.path() == "/watch" {
-                    if let Some(id) = url.query_pairs()
-                        .find(|(key, _)| key == "v")
-                        .map(|(_, value)| value.to_string()) {
-                        return Ok(id);
-                    }
-                }
-                
-                // Handle /shorts/ format
-                if let Some(segments) = url.path_segments() {
-                    let segments: Vec<_> = segments.collect();
-                    if segments.get(0) == Some(&"shorts") {
-                        if let Some(id) = segments.get(1) {
-                            return Ok(id.to_string());
-                        }
-                    }
-                }
-            }
+        let id = match (host, segments.as_slice()) {
+            ("youtu.be", [id, ..]) => id.to_string(),
+            (_, ["watch"]) => url.query_pairs()
+                .find(|(key, _)| key == "v")
+                .map(|(_, value)| value.into_owned())
+                .ok_or_else(invalid)?,
+            (_, ["clip", _]) => return Ok(VideoTarget::Clip(url.clone())),
+            (_, ["shorts" | "embed" | "live" | "v" | "e", id, ..]) => id.to_string(),
+            _ => return Err(invalid()),
+        };
+
+        if !Self::is_video_id(&id) {
+            return Err(invalid());
         }
 
-        Err(Error::InvalidUrl("No video ID found in URL".into()))
+        let query = |name: &str| url.query_pairs().find(|(key, _)| key == name).map(|(_, value)| value.into_owned());
+        let fragment_time = url.fragment()
+            .and_then(|fragment| fragment.split('&').find_map(|part| part.strip_prefix("t=")))
+            .map(str::to_string);
+
+        let start = query("t").or_else(|| query("start")).or(fragment_time)
+            .and_then(|t| Self::parse_url_time(&t))
+            .filter(|t| *t > 0.0);
+        let end = query("end").and_then(|t| Self::parse_url_time(&t));
+
+        Ok(VideoTarget::Video { id, start, end })
     }
 
-    /// Get available video formats using yt-dlp
-    async fn get_format_info(&self, video_id: &str) -> Result<Vec<VideoFormat>> {
-        // Run yt-dlp to get available formats
-        let output = Command::new("yt-dlp")
-            .args([
-                "-F",
-                "--format-sort", "hasvideohasaudio,res,fps,codec:h264",
-                format!("https://www.youtube.com/watch?v={}", video_id).as_str()
-            ])
-            .output()
-            .await
-            .map_err(|e| Error::CommandExecution {
-                command: "yt-dlp".to_string(),
-                reason: e.to_string()
-            })?;
+    /// Video IDs are 11 characters of URL-safe base64
+    fn is_video_id(id: &str) -> bool {
+        id.len() == 11 && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    }
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(Error::CommandExecution {
-                command: "yt-dlp".to_string(),
-                reason: stderr.to_string()
-            });
+    /// Parse a URL time such as `90`, `90s`, `1m30s`, `1h2m3s` or `1:30`
+    fn parse_url_time(value: &str) -> Option<f64> {
+        if let Some(seconds) = parse_time(value) {
+            return Some(seconds);
         }
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let mut formats = Vec::new();
-        
-        // Process all lines of output
-        for line in stdout.lines() {
-            // Skip header lines and separator lines
-            if line.starts_with("ID") || line.starts_with("--") || line.trim().is_empty() {
-                continue;
-            }
-            
-            // Parse format information
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() < 3 {
-                continue; // Not enough parts to be a format line
-            }
-            
-            // Extract basic fields
-            let id = parts[0].to_string();
-            let ext = parts[1].to_string();
-            
-            // Skip audio-only formats
-            if line.contains("audio only") {
-                continue;
-            }
-            
-            // Determine quality from resolution
-            let quality = self.determine_quality(line);
-            
-            // Determine format based on extension
-            let format = match ext.as_str() {
-                "mp4" => Format::MP4,
-                "webm" => Format::WebM,
-                "mov" => Format::MOV,
-                _ => Format::Other(ext.clone()),
-            };
-            
-            // Add the format
-            formats.push(VideoFormat {
-                id,
-                quality,
-                format,
-                file_size: None,
-            });
+        let re = URL_TIME.get_or_init(|| Regex::new(r"^(?:(\d+)h)?(?:(\d+)m)?(?:(\d+(?:\.\d+)?)s)?$").unwrap());
+        let caps = re.captures(value)?;
+        if caps.get(0)?.as_str().is_empty() {
+            return None;
         }
-        
-        // Always add a "best" format that lets yt-dlp choose
-        formats.push(VideoFormat {
-            id: "best".to_string(),
-            quality: Quality::HD1080,
-            format: Format::MP4,
-            file_size: None,
+        let part = |index: usize| caps.get(index).and_then(|m| m.as_str().parse::<f64>().ok()).unwrap_or(0.0);
+        Some(part(1) * 3600.0 + part(2) * 60.0 + part(3))
+    }
+
+    fn watch_url(id: &str) -> String {
+        format!("https://www.youtube.com/watch?v={}", id)
+    }
+
+    /// Parse one line of `yt-dlp --flat-playlist --dump-json` output into a playlist entry
+    fn parse_flat_entry(line: &str) -> Result<PlaylistEntry> {
+        let entry: serde_json::Value = serde_json::from_str(line)
+            .map_err(|e| Error::OutputParsing(format!("Failed to parse playlist entry: {}", e)))?;
+
+        let url = match (entry["url"].as_str(), entry["id"].as_str()) {
+            (Some(url), _) if url.starts_with("http") => url.to_string(),
+            (_, Some(id)) => Self::watch_url(id),
+            _ => return Err(Error::OutputParsing("Playlist entry has no URL or ID".into())),
+        };
+
+        Ok(PlaylistEntry {
+            url: Url::parse(&url).map_err(|_| Error::InvalidUrl(url.clone()))?,
+            title: entry["title"].as_str().map(|s| s.to_string()),
+        })
+    }
+
+    /// Convert yt-dlp's formats, best first: streams with both video and audio, then
+    /// video-only and audio-only streams, each ordered by resolution and bitrate.
+    /// Storyboards and other formats without media streams are skipped.
+    fn build_formats(formats: &[YtDlpFormat]) -> Result<Vec<VideoFormat>> {
+        let mut formats: Vec<_> = formats.iter()
+            .filter(|f| f.has_video() || f.has_audio())
+            .collect();
+
+        formats.sort_by(|a, b| {
+            let key = |f: &YtDlpFormat| (f.has_video() && f.has_audio(), f.has_video(), f.height.unwrap_or(0));
+            key(b).cmp(&key(a))
+                .then_with(|| b.tbr.unwrap_or(0.0).total_cmp(&a.tbr.unwrap_or(0.0)))
         });
-        
-        // Return error if no formats were found (except for the "best" we added)
-        if formats.len() <= 1 {
+
+        if formats.is_empty() {
             return Err(Error::NoSuitableFormats);
         }
-        
-        Ok(formats)
+
+        Ok(formats.into_iter().map(YtDlpFormat::to_video_format).collect())
     }
     
-    /// Helper to determine video quality from format description
-    fn determine_quality(&self, format_line: &str) -> Quality {
-        if format_line.contains("3840x2160") || format_line.contains("2160p") {
-            Quality::UHD2160
-        } else if format_line.contains("1920x1080") || format_line.contains("1080p") {
-            Quality::HD1080
-        } else if format_line.contains("1280x720") || format_line.contains("720p") {
-            Quality::HD720
-        } else if format_line.contains("854x480") || format_line.contains("480p") {
-            Quality::Medium
-        } else if format_line.contains("640x360") || format_line.contains("360p") {
-            Quality::Low
-        } else if format_line.contains("426x240") || format_line.contains("240p") {
-            Quality::Low
-        } else {
-            // Default to medium quality if we can't determine
-            Quality::Medium
-        }
+    /// One track per language, preferring the VTT rendition, then SRT
+    fn build_subtitles(tracks: &BTreeMap<String, Vec<YtDlpSubtitle>>, auto_generated: bool) -> Vec<SubtitleTrack> {
+        tracks.iter()
+            .filter_map(|(language, renditions)| {
+                // Only formats the subtitle post-processor can convert; json3, srv3 and
+                // ttml renditions are skipped
+                let rendition = ["vtt", "srt"].iter()
+                    .find_map(|ext| renditions.iter().find(|r| r.ext == *ext))?;
+
+                Some(SubtitleTrack {
+                    language: language.clone(),
+                    name: rendition.name.clone(),
+                    url: Url::parse(&rendition.url).ok()?,
+                    ext: rendition.ext.clone(),
+                    auto_generated,
+                })
+            })
+            .collect()
     }
-    
+
     /// Get video metadata using yt-dlp
-    async fn fetch_metadata(&self, video_id: &str) -> Result<YouTubeMetadata> {
-        let url = format!("https://www.youtube.com/watch?v={}", video_id);
-        
+    async fn fetch_metadata(&self, url: &str) -> Result<YouTubeMetadata> {
         let output = Command::new("yt-dlp")
             .args(["--dump-json", "--no-playlist"])
-            .arg(&url)
+            .arg(url)
             .output()
             .await
             .map_err(|e| Error::CommandExecution {
@@ -206,25 +313,176 @@ impl Platform for YouTube {
 
     fn supports_url(&self, url: &Url) -> bool {
         url.host_str()
-            .map(|host| host.ends_with("youtube.com") || host == "youtu.be")
+            .map(|host| host.ends_with("youtube.com") || host.ends_with("youtube-nocookie.com") || host == "youtu.be")
             .unwrap_or(false)
     }
 
+    fn video_id(&self, url: &Url) -> Option<String> {
+        match Self::parse_video_url(url) {
+            Ok(VideoTarget::Video { id, .. }) => Some(id),
+            _ => None,
+        }
+    }
+
+    fn is_collection(&self, url: &Url) -> bool {
+        if url.host_str() == Some("youtu.be") {
+            return false;
+        }
+
+        let path = url.path();
+        (path == "/playlist" && url.query_pairs().any(|(key, _)| key == "list"))
+            || path.starts_with("/@")
+            || path.starts_with("/channel/")
+            || path.starts_with("/c/")
+            || path.starts_with("/user/")
+    }
+
+    fn expand<'a>(&'a self, url: &'a Url, range: &'a PlaylistRange) -> BoxStream<'a, Result<PlaylistEntry>> {
+        // yt-dlp selects the range itself so entries before the start are never listed,
+        // and --lazy-playlist makes it print entries as each page arrives
+        let (first, last) = range.bounds();
+        let items = format!("{}:{}", first, last.map(|last| last.to_string()).unwrap_or_default());
+        let spawned = Command::new("yt-dlp")
+            .args(["--flat-playlist", "--lazy-playlist", "--dump-json", "--no-warnings"])
+            .args(["--playlist-items", &items])
+            .arg(url.as_str())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn();
+
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(e) => return stream::once(future::ready(Err(command_err("yt-dlp", e.to_string())))).boxed(),
+        };
+
+        let (lines, stderr) = match (child.stdout.take(), child.stderr.take()) {
+            (Some(stdout), Some(mut stderr)) => {
+                // Drain stderr while stdout is read, so a chatty run over a long channel
+                // can't fill the pipe and stall yt-dlp
+                let drain = tokio::spawn(async move {
+                    let mut text = String::new();
+                    let _ = stderr.read_to_string(&mut text).await;
+                    text
+                });
+                (BufReader::new(stdout).lines(), drain)
+            }
+            _ => return stream::once(future::ready(Err(command_err("yt-dlp", "output not captured")))).boxed(),
+        };
+
+        stream::unfold(Some((child, lines, stderr)), |state| async move {
+            let (mut child, mut lines, stderr) = state?;
+            loop {
+                match lines.next_line().await {
+                    Ok(Some(line)) if line.trim().is_empty() => continue,
+                    Ok(Some(line)) => return Some((Self::parse_flat_entry(&line), Some((child, lines, stderr)))),
+                    Ok(None) => break,
+                    Err(e) => return Some((Err(Error::IO(e)), None)),
+                }
+            }
+
+            // Output is exhausted; surface a failure if yt-dlp could not list the playlist
+            match child.wait().await {
+                Ok(status) if !status.success() => {
+                    let stderr = stderr.await.unwrap_or_default();
+                    Some((Err(command_err("yt-dlp", stderr)), None))
+                }
+                Ok(_) => None,
+                Err(e) => Some((Err(command_err("yt-dlp", e.to_string())), None)),
+            }
+        })
+        .boxed()
+    }
+
     async fn extract_info(&self, url: &Url) -> Result<VideoInfo> {
-        let video_id = self.extract_video_id(url).await?;
-        let metadata = self.fetch_metadata(&video_id).await?;
-        let formats = self.get_format_info(&video_id).await?;
+        let (metadata, start, end) = match Self::parse_video_url(url)? {
+            VideoTarget::Video { id, start, end } => {
+                (self.fetch_metadata(&Self::watch_url(&id)).await?, start, end)
+            }
+            VideoTarget::Clip(clip) => {
+                let metadata = self.fetch_metadata(clip.as_str()).await?;
+                let (start, end) = (metadata.section_start, metadata.section_end);
+                (metadata, start, end)
+            }
+        };
+        let section = match (start, end) {
+            (None, None) => None,
+            (start, end) => Some(Section { start: start.unwrap_or(0.0), end, accurate: false }),
+        };
+
+        // Download from the canonical watch page whichever form of URL was given
+        let video_url = match metadata.id.as_str() {
+            "" => url.clone(),
+            id => Url::parse(&Self::watch_url(id)).map_err(|_| Error::InvalidUrl(id.to_string()))?,
+        };
+        let formats = Self::build_formats(&metadata.formats)?;
+
+        let thumbnails = metadata.thumbnails.iter()
+            .filter_map(|t| Some(Thumbnail {
+                url: Url::parse(&t.url).ok()?,
+                width: t.width,
+                height: t.height,
+            }))
+            .collect();
+
+        let chapters = metadata.chapters.unwrap_or_default().into_iter()
+            .map(|c| Chapter { title: c.title, start: c.start_time, end: c.end_time })
+            .collect();
+
+        let mut subtitles = Self::build_subtitles(&metadata.subtitles, false);
+        subtitles.extend(Self::build_subtitles(&metadata.automatic_captions, true));
 
         Ok(VideoInfo {
-            url: url.clone(),
+            url: video_url.clone(),
+            id: Some(metadata.id).filter(|id| !id.is_empty()),
+            platform: self.name().to_string(),
+            webpage_url: metadata.webpage_url.and_then(|u| Url::parse(&u).ok()).or(Some(video_url)),
             title: metadata.title,
             description: metadata.description,
             duration: metadata.duration.map(|d| d as u64),
             formats,
+            subtitles,
+            thumbnails,
+            uploader: metadata.uploader,
+            uploader_id: metadata.uploader_id.or(metadata.channel_id),
+            upload_date: metadata.upload_date,
+            view_count: metadata.view_count,
+            like_count: metadata.like_count,
+            comment_count: metadata.comment_count,
+            tags: metadata.tags,
+            categories: metadata.categories,
+            chapters,
+            section,
         })
     }
 
     async fn download_video(&self, info: &VideoInfo, format_id: &str, output_path: &Path, progress_tx: Arc<watch::Sender<f64>>) -> Result<()> {
+        self.run_ytdlp(info, format_id, output_path, None, progress_tx).await
+    }
+
+    async fn download_section(
+        &self,
+        info: &VideoInfo,
+        format_id: &str,
+        section: &Section,
+        output_path: &Path,
+        progress_tx: Arc<watch::Sender<f64>>,
+    ) -> Result<bool> {
+        self.run_ytdlp(info, format_id, output_path, Some(section), progress_tx).await?;
+        Ok(true)
+    }
+}
+
+impl YouTube {
+    /// Download a format with yt-dlp, restricted to `section` when given
+    async fn run_ytdlp(
+        &self,
+        info: &VideoInfo,
+        format_id: &str,
+        output_path: &Path,
+        section: Option<&Section>,
+        progress_tx: Arc<watch::Sender<f64>>
+    ) -> Result<()> {
         let output_str = output_path.to_str()
             .ok_or_else(|| Error::InvalidOutputPath(output_path.to_path_buf()))?;
 
@@ -246,6 +504,9 @@ impl Platform for YouTube {
             // If ffmpeg is available, we can download video and audio separately and merge
             if format_id == "best" {
                 "bestvideo[ext=mp4]+bestaudio[ext=m4a]/best[ext=mp4]/best".to_string()
+            } else if info.formats.iter().any(|f| f.id == format_id && f.has_video && !f.has_audio) {
+                // Video-only streams get the best audio merged in
+                format!("{}+bestaudio/{}/best", format_id, format_id)
             } else {
                 // Fix: Properly wrap format ID in quotes to avoid it being interpreted as a filter
                 format!("{}/best", format_id)
@@ -258,18 +519,10 @@ impl Platform for YouTube {
             } else {
                 // For specific format IDs, we need to find a suitable alternative with audio included
                 match info.formats.iter().find(|f| f.id == format_id) {
-                    Some(format) => {
-                        let quality_label = match format.quality {
-                            Quality::UHD2160 => "2160",
-                            Quality::HD1080 => "1080",
-                            Quality::HD720 => "720",
-                            Quality::High => "720",  // Treat 'High' as equivalent to 720p
-                            Quality::Medium => "480",
-                            Quality::Low => "360",
-                            Quality::Custom(ref s) => s, // Use the custom quality string
-                        };
+                    Some(format) => match format.quality.max_height() {
                         // Fix: Use numeric ID directly instead of filter expression
-                        format!("{}/best[height<={}][ext=mp4]/best", format_id, quality_label)
+                        Some(height) => format!("{}/best[height<={}][ext=mp4]/best", format_id, height),
+                        None => format!("{}/best[ext=mp4]/best", format_id),
                     },
                     None => "best[ext=mp4]/best".to_string()
                 }
@@ -288,10 +541,20 @@ impl Platform for YouTube {
             cmd.args(["--merge-output-format", "mp4"]);
         }
 
+        if let Some(section) = section {
+            let end = section.end.map(|end| end.to_string()).unwrap_or_else(|| "inf".to_string());
+            cmd.args(["--download-sections", &format!("*{}-{}", section.start, end)]);
+            if section.accurate {
+                cmd.arg("--force-keyframes-at-cuts");
+            }
+        }
+
         // Add additional options for consistent progress reporting
         cmd.args([
             "--newline",
             "--no-part",
+            // Callers decide what happens to existing files before downloading
+            "--force-overwrites",
             "--no-colors",
             "--quiet",
             "--progress",
@@ -368,3 +631,56 @@ impl Platform for YouTube {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_formats_from_json() {
+        let formats: Vec<YtDlpFormat> = serde_json::from_str(r#"[
+            {"format_id": "sb0", "ext": "mhtml", "vcodec": "none", "acodec": "none"},
+            {"format_id": "140", "ext": "m4a", "vcodec": "none", "acodec": "mp4a.40.2", "abr": 129.5, "filesize": 3000},
+            {"format_id": "18", "ext": "mp4", "height": 360, "vcodec": "avc1.42001E", "acodec": "mp4a.40.2", "tbr": 500.0},
+            {"format_id": "137", "ext": "mp4", "height": 1080, "vcodec": "avc1.640028", "acodec": "none", "tbr": 4000.0, "filesize_approx": 90000}
+        ]"#).unwrap();
+
+        let formats = YouTube::build_formats(&formats).unwrap();
+        let ids: Vec<_> = formats.iter().map(|f| f.id.as_str()).collect();
+
+        assert_eq!(ids, ["18", "137", "140"]);
+        assert_eq!(formats[1].quality, Quality::HD1080);
+        assert_eq!(formats[1].file_size, Some(90000));
+        assert!(formats[1].has_video && !formats[1].has_audio);
+        assert_eq!(formats[1].video_bitrate, Some(4000));
+        assert!(!formats[2].has_video && formats[2].has_audio);
+        assert_eq!(formats[2].quality, Quality::Custom("audio 130k".to_string()));
+    }
+
+    #[test]
+    fn test_parse_video_url() {
+        let parse = |url: &str| YouTube::parse_video_url(&Url::parse(url).unwrap());
+        let video = |start, end| VideoTarget::Video { id: "dQw4w9WgXcQ".to_string(), start, end };
+
+        for url in [
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=PL123",
+            "https://m.youtube.com/watch?v=dQw4w9WgXcQ",
+            "https://music.youtube.com/watch?v=dQw4w9WgXcQ&feature=share",
+            "https://youtu.be/dQw4w9WgXcQ?si=abc",
+            "https://www.youtube.com/shorts/dQw4w9WgXcQ",
+            "https://www.youtube.com/live/dQw4w9WgXcQ?feature=shared",
+            "https://www.youtube.com/v/dQw4w9WgXcQ",
+            "https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ",
+        ] {
+            assert_eq!(parse(url).unwrap(), video(None, None), "{}", url);
+        }
+
+        assert_eq!(parse("https://youtu.be/dQw4w9WgXcQ?t=90").unwrap(), video(Some(90.0), None));
+        assert_eq!(parse("https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=1h2m3s").unwrap(), video(Some(3723.0), None));
+        assert_eq!(parse("https://www.youtube.com/watch?v=dQw4w9WgXcQ#t=1m30s").unwrap(), video(Some(90.0), None));
+        assert_eq!(parse("https://www.youtube.com/embed/dQw4w9WgXcQ?start=30&end=45").unwrap(), video(Some(30.0), Some(45.0)));
+        assert!(matches!(parse("https://www.youtube.com/clip/UgkxU2HSeGL_NvmDJ-nQJrlLwllwMDBdGZFs").unwrap(), VideoTarget::Clip(_)));
+        assert!(parse("https://www.youtube.com/watch?v=short").is_err());
+        assert!(parse("https://www.youtube.com/feed/trending").is_err());
+    }
+}
@@ -0,0 +1,150 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::utils::ffmpeg;
+use crate::{Error, Result};
+
+/// Bitrate used for lossy formats when none is requested
+const DEFAULT_BITRATE: &str = "192k";
+
+/// Audio formats `--extract-audio` can produce
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AudioFormat {
+    Mp3,
+    M4a,
+    Opus,
+    Flac,
+}
+
+impl AudioFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::M4a => "m4a",
+            AudioFormat::Opus => "opus",
+            AudioFormat::Flac => "flac",
+        }
+    }
+
+    fn encoder(self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "libmp3lame",
+            AudioFormat::M4a => "aac",
+            AudioFormat::Opus => "libopus",
+            AudioFormat::Flac => "flac",
+        }
+    }
+
+    /// Codec of the format as ffprobe names it
+    fn codec(self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::M4a => "aac",
+            AudioFormat::Opus => "opus",
+            AudioFormat::Flac => "flac",
+        }
+    }
+
+    fn is_lossless(self) -> bool {
+        self == AudioFormat::Flac
+    }
+}
+
+impl FromStr for AudioFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "mp3" => Ok(AudioFormat::Mp3),
+            "m4a" | "aac" => Ok(AudioFormat::M4a),
+            "opus" => Ok(AudioFormat::Opus),
+            "flac" => Ok(AudioFormat::Flac),
+            other => Err(Error::InvalidArgument(format!("Unsupported audio format: {}", other))),
+        }
+    }
+}
+
+/// How to convert a download into an audio-only file
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioOptions {
+    pub format: AudioFormat,
+    /// Target bitrate such as `192k`; ignored for lossless formats
+    pub bitrate: Option<String>,
+}
+
+impl AudioOptions {
+    pub fn new(format: AudioFormat, bitrate: Option<String>) -> Result<Self> {
+        let bitrate = bitrate.map(|b| normalize_bitrate(&b)).transpose()?;
+        Ok(Self { format, bitrate })
+    }
+}
+
+/// Accept bitrates such as `192`, `192k` or `192K`, returning ffmpeg's `192k` form
+fn normalize_bitrate(bitrate: &str) -> Result<String> {
+    let digits = bitrate.trim().trim_end_matches(['k', 'K']);
+    match digits.parse::<u32>() {
+        Ok(kbps) if kbps > 0 => Ok(format!("{}k", kbps)),
+        _ => Err(Error::InvalidArgument(format!("Invalid audio bitrate: {}", bitrate))),
+    }
+}
+
+/// Convert the audio track of `input` into a file of the requested format next to it,
+/// removing the original download.
+///
+/// Audio already in the target codec is copied rather than re-encoded, unless a bitrate
+/// was requested, to avoid another lossy pass.
+pub async fn extract(input: &Path, options: &AudioOptions) -> Result<PathBuf> {
+    let output = output_path(input, options);
+
+    // Convert into a temporary file when the download already has the target name
+    let target = if output == input {
+        input.with_extension(format!("audio.{}", options.format.extension()))
+    } else {
+        output.clone()
+    };
+
+    let copy = options.bitrate.is_none()
+        && ffmpeg::audio_codec(input).await.ok().flatten().as_deref() == Some(options.format.codec());
+
+    let mut args: Vec<&OsStr> = vec![OsStr::new("-i"), input.as_os_str()];
+    if copy {
+        args.extend(["-vn", "-c:a", "copy"].map(OsStr::new));
+    } else {
+        args.extend(["-vn", "-c:a", options.format.encoder()].map(OsStr::new));
+    }
+    if !copy && !options.format.is_lossless() {
+        let bitrate = options.bitrate.as_deref().unwrap_or(DEFAULT_BITRATE);
+        args.extend(["-b:a", bitrate].map(OsStr::new));
+    }
+    args.push(target.as_os_str());
+
+    ffmpeg::run(args).await?;
+
+    tokio::fs::remove_file(input).await?;
+    if target != output {
+        tokio::fs::rename(&target, &output).await?;
+    }
+
+    Ok(output)
+}
+
+/// Path of the audio file extracted from `input`
+pub fn output_path(input: &Path, options: &AudioOptions) -> PathBuf {
+    input.with_extension(options.format.extension())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audio_options() {
+        assert_eq!("M4A".parse::<AudioFormat>().unwrap(), AudioFormat::M4a);
+        assert!("wav".parse::<AudioFormat>().is_err());
+
+        let options = AudioOptions::new(AudioFormat::Mp3, Some("320".to_string())).unwrap();
+        assert_eq!(options.bitrate.as_deref(), Some("320k"));
+        assert!(AudioOptions::new(AudioFormat::Mp3, Some("loud".to_string())).is_err());
+    }
+}
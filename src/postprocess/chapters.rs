@@ -0,0 +1,83 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use log::warn;
+
+use crate::utils::ffmpeg;
+use crate::{Chapter, Result, VideoInfo};
+
+/// How far a chapter start may be from a keyframe for a stream-copied cut to begin on it
+const KEYFRAME_TOLERANCE: f64 = 0.05;
+
+/// Cut `file` into one file per chapter of `info`, named `<stem> - <NNN> <title>.<ext>`.
+///
+/// Chapters are stream-copied when they start on a keyframe (or the file has no video);
+/// other chapters have their video re-encoded so they start exactly at the chapter mark.
+pub async fn split(info: &VideoInfo, file: &Path) -> Result<Vec<PathBuf>> {
+    if info.chapters.is_empty() {
+        warn!("No chapters found in {}, not splitting", info.title);
+        return Ok(Vec::new());
+    }
+
+    let keyframes = ffmpeg::keyframes(file).await?;
+    let mut files = Vec::new();
+
+    for (index, chapter) in info.chapters.iter().enumerate() {
+        let output = chapter_path(file, index, chapter);
+        let copy = keyframes.is_empty() || starts_on_keyframe(chapter, &keyframes);
+
+        let mut args: Vec<String> = vec![
+            "-ss".into(), chapter.start.to_string(),
+            "-i".into(), file.to_string_lossy().into_owned(),
+            "-t".into(), (chapter.end - chapter.start).to_string(),
+            "-map".into(), "0".into(),
+            "-dn".into(),
+            "-c".into(), "copy".into(),
+        ];
+        if !copy {
+            args.extend(ffmpeg::video_encoder_args(file).iter().map(|arg| arg.to_string()));
+        }
+        args.extend(["-avoid_negative_ts".into(), "make_zero".into(), "-metadata".into(), format!("title={}", chapter.title)]);
+        args.push(output.to_string_lossy().into_owned());
+
+        ffmpeg::run(args).await?;
+        files.push(output);
+    }
+
+    Ok(files)
+}
+
+fn starts_on_keyframe(chapter: &Chapter, keyframes: &[f64]) -> bool {
+    keyframes.iter().any(|k| (k - chapter.start).abs() <= KEYFRAME_TOLERANCE)
+}
+
+fn chapter_path(file: &Path, index: usize, chapter: &Chapter) -> PathBuf {
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = file.extension().and_then(OsStr::to_str).unwrap_or("mp4");
+    file.with_file_name(format!(
+        "{} - {:03} {}.{}",
+        stem,
+        index + 1,
+        sanitize_filename::sanitize(&chapter.title),
+        extension
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chapter_cuts() {
+        let chapter = |title: &str, start| Chapter { title: title.to_string(), start, end: start + 60.0 };
+        let keyframes = [0.0, 2.002, 60.02, 124.0];
+
+        assert!(starts_on_keyframe(&chapter("Intro", 0.0), &keyframes));
+        assert!(starts_on_keyframe(&chapter("Verse", 60.0), &keyframes));
+        assert!(!starts_on_keyframe(&chapter("Outro", 120.0), &keyframes));
+
+        assert_eq!(
+            chapter_path(Path::new("out/Song.mp4"), 1, &chapter("Verse 1/2", 60.0)),
+            Path::new("out/Song - 002 Verse 12.mp4")
+        );
+    }
+}
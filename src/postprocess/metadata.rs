@@ -0,0 +1,109 @@
+use std::ffi::OsStr;
+use std::path::Path;
+
+use crate::utils::ffmpeg;
+use crate::{Chapter, Result, VideoInfo};
+
+/// Write the title, uploader, date, description and source URL of `info` into the container
+/// tags of `file`, along with its chapters, replacing the original
+pub async fn embed(info: &VideoInfo, file: &Path) -> Result<()> {
+    let extension = file.extension().and_then(OsStr::to_str).unwrap_or("mp4").to_lowercase();
+    let temp = file.with_extension(format!("meta.{}", extension));
+    let chapters_file = file.with_extension("chapters.txt");
+
+    let mut args: Vec<String> = vec!["-i".into(), file.to_string_lossy().into_owned()];
+    if !info.chapters.is_empty() {
+        tokio::fs::write(&chapters_file, ffmetadata(&info.chapters)).await?;
+        args.extend([
+            "-f".into(), "ffmetadata".into(),
+            "-i".into(), chapters_file.to_string_lossy().into_owned(),
+            "-map_chapters".into(), "1".into(),
+        ]);
+    }
+    args.extend(["-map".into(), "0".into(), "-dn".into(), "-c".into(), "copy".into()]);
+    for (key, value) in tags(info) {
+        args.extend(["-metadata".into(), format!("{}={}", key, value)]);
+    }
+    // Otherwise ffmpeg writes ID3v2.4, which many players ignore
+    if extension == "mp3" {
+        args.extend(["-id3v2_version".into(), "3".into()]);
+    }
+    args.push(temp.to_string_lossy().into_owned());
+
+    let result = ffmpeg::run(args).await;
+    let _ = tokio::fs::remove_file(&chapters_file).await;
+    if let Err(e) = result {
+        let _ = tokio::fs::remove_file(&temp).await;
+        return Err(e);
+    }
+
+    tokio::fs::rename(&temp, file).await?;
+    Ok(())
+}
+
+/// Container tags for `info`; the description and URL are duplicated under the names
+/// that MP4 and Matroska players look for
+fn tags(info: &VideoInfo) -> Vec<(&'static str, String)> {
+    let mut tags = vec![("title", info.title.clone())];
+
+    if let Some(uploader) = &info.uploader {
+        tags.push(("artist", uploader.clone()));
+    }
+    if let Some(date) = &info.upload_date {
+        tags.push(("date", date.clone()));
+    }
+    if let Some(description) = info.description.as_ref().filter(|d| !d.is_empty()) {
+        tags.push(("description", description.clone()));
+        tags.push(("synopsis", description.clone()));
+    }
+    tags.push(("purl", info.url.to_string()));
+    tags.push(("comment", info.url.to_string()));
+
+    tags
+}
+
+/// Chapters in ffmpeg's metadata file format, with millisecond times
+fn ffmetadata(chapters: &[Chapter]) -> String {
+    let mut output = String::from(";FFMETADATA1\n");
+    for chapter in chapters {
+        output.push_str(&format!(
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            (chapter.start * 1000.0).round() as u64,
+            (chapter.end * 1000.0).round() as u64,
+            escape(&chapter.title)
+        ));
+    }
+    output
+}
+
+/// Escape the characters that are special in ffmetadata values
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ffmetadata() {
+        let chapters = vec![
+            Chapter { title: "Intro".to_string(), start: 0.0, end: 61.5 },
+            Chapter { title: "Q&A; part=2".to_string(), start: 61.5, end: 120.0 },
+        ];
+
+        assert_eq!(
+            ffmetadata(&chapters),
+            ";FFMETADATA1\n\
+             [CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=61500\ntitle=Intro\n\
+             [CHAPTER]\nTIMEBASE=1/1000\nSTART=61500\nEND=120000\ntitle=Q&A\\; part\\=2\n"
+        );
+    }
+}
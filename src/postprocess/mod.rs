@@ -0,0 +1,77 @@
+pub mod audio;
+pub mod chapters;
+pub mod metadata;
+pub mod subtitles;
+pub mod thumbnail;
+
+use std::path::{Path, PathBuf};
+use log::info;
+
+use crate::{Result, VideoInfo};
+use audio::AudioOptions;
+use subtitles::SubtitleOptions;
+use thumbnail::ThumbnailOptions;
+
+/// Steps applied to a file once it has been downloaded
+#[derive(Debug, Clone, Default)]
+pub struct PostProcessor {
+    /// Replace the download with an audio-only file
+    pub extract_audio: Option<AudioOptions>,
+    /// Download subtitles as sidecar files and/or embed them
+    pub subtitles: Option<SubtitleOptions>,
+    /// Write title, uploader, date, description, source URL and chapters into the file's tags
+    pub embed_metadata: bool,
+    /// Save the thumbnail as a sidecar file and/or embed it as cover art
+    pub thumbnail: Option<ThumbnailOptions>,
+    /// Also cut the final file into one file per chapter
+    pub split_chapters: bool,
+    /// Write an `.info.json` record of the download next to the final file; this is done by
+    /// the caller, which knows the source URL and platform
+    pub write_info_json: bool,
+}
+
+impl PostProcessor {
+    /// Path of the final file for a download to `path`
+    pub fn final_path(&self, path: &Path) -> PathBuf {
+        match &self.extract_audio {
+            Some(options) => audio::output_path(path, options),
+            None => path.to_path_buf(),
+        }
+    }
+
+    /// Run the configured steps on `path`, returning the path of the final file
+    pub async fn run(&self, info: &VideoInfo, path: PathBuf) -> Result<PathBuf> {
+        let mut path = path;
+
+        if let Some(options) = &self.subtitles {
+            // Audio files get sidecars only
+            let options = SubtitleOptions {
+                embed: options.embed && self.extract_audio.is_none(),
+                ..options.clone()
+            };
+            subtitles::process(info, &path, &options).await?;
+        }
+
+        if let Some(options) = &self.extract_audio {
+            path = audio::extract(&path, options).await?;
+        }
+
+        if self.embed_metadata {
+            metadata::embed(info, &path).await?;
+        }
+
+        // Cover art goes on the final file, after any audio extraction
+        if let Some(options) = &self.thumbnail {
+            thumbnail::process(info, &path, options).await?;
+        }
+
+        // The complete file is kept alongside its chapters
+        if self.split_chapters {
+            for file in chapters::split(info, &path).await? {
+                info!("Chapter saved: {:?}", file);
+            }
+        }
+
+        Ok(path)
+    }
+}
@@ -0,0 +1,330 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use log::warn;
+use regex::Regex;
+use reqwest::Client;
+
+use crate::utils::ffmpeg;
+use crate::{Error, Result, SubtitleTrack, VideoInfo};
+
+/// Subtitle file formats that can be written
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+}
+
+impl SubtitleFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            SubtitleFormat::Srt => "srt",
+            SubtitleFormat::Vtt => "vtt",
+        }
+    }
+}
+
+impl FromStr for SubtitleFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "srt" => Ok(SubtitleFormat::Srt),
+            "vtt" | "webvtt" => Ok(SubtitleFormat::Vtt),
+            other => Err(Error::InvalidArgument(format!("Unsupported subtitle format: {}", other))),
+        }
+    }
+}
+
+/// Which subtitle tracks to fetch and what to do with them
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubtitleOptions {
+    /// Language codes such as `en` or `pt-BR`; `all` selects every track
+    pub languages: Vec<String>,
+    /// Fall back to automatically generated captions for languages without manual subtitles
+    pub include_auto: bool,
+    pub format: SubtitleFormat,
+    /// Keep the subtitles as sidecar files next to the video
+    pub write: bool,
+    /// Mux the subtitles into the video as soft subtitle streams
+    pub embed: bool,
+}
+
+/// A subtitle cue with times in milliseconds
+#[derive(Debug, Clone, PartialEq)]
+struct Cue {
+    start: u64,
+    end: u64,
+    text: String,
+}
+
+/// Pick one track per requested language, preferring manual subtitles and exact language
+/// matches (`en`) over regional ones (`en-US`). Tracks in formats other than SRT and
+/// WebVTT are skipped, since they can't be converted.
+pub fn select_tracks<'a>(tracks: &'a [SubtitleTrack], options: &SubtitleOptions) -> Vec<&'a SubtitleTrack> {
+    let usable = |track: &&SubtitleTrack| {
+        (options.include_auto || !track.auto_generated) && track.ext.parse::<SubtitleFormat>().is_ok()
+    };
+
+    if options.languages.iter().any(|l| l.eq_ignore_ascii_case("all")) {
+        let manual: Vec<_> = tracks.iter().filter(usable).filter(|t| !t.auto_generated).collect();
+        let auto = tracks.iter()
+            .filter(usable)
+            .filter(|t| t.auto_generated && !manual.iter().any(|m| m.language == t.language));
+        return manual.iter().copied().chain(auto).collect();
+    }
+
+    options.languages.iter()
+        .filter_map(|language| {
+            let matches = |track: &&SubtitleTrack| {
+                track.language.eq_ignore_ascii_case(language)
+                    || track.language.to_lowercase().starts_with(&format!("{}-", language.to_lowercase()))
+            };
+
+            tracks.iter()
+                .filter(usable)
+                .filter(matches)
+                .min_by_key(|track| (track.auto_generated, !track.language.eq_ignore_ascii_case(language)))
+        })
+        .collect()
+}
+
+/// Download the selected subtitles of `info` for the video at `video`, writing sidecars
+/// named `<video stem>.<language>.<ext>` and embedding them when requested.
+///
+/// Subtitles are extras: tracks that can't be fetched or converted, and a failed embed,
+/// are reported as warnings without failing the download.
+pub async fn process(info: &VideoInfo, video: &Path, options: &SubtitleOptions) -> Result<()> {
+    let tracks = select_tracks(&info.subtitles, options);
+    if tracks.is_empty() {
+        warn!("No subtitles found for the requested languages of {}", info.title);
+        return Ok(());
+    }
+
+    let client = Client::new();
+    let mut files = Vec::new();
+
+    for track in tracks {
+        match save_track(&client, track, video, options.format).await {
+            Ok(path) => files.push((path, track.language.clone())),
+            Err(e) => warn!("Skipping {} subtitles: {}", track.language, e),
+        }
+    }
+
+    if options.embed && !files.is_empty() {
+        if let Err(e) = embed(video, &files).await {
+            warn!("Could not embed subtitles: {}", e);
+        }
+    }
+
+    if !options.write {
+        for (path, _) in &files {
+            let _ = tokio::fs::remove_file(path).await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Fetch a track and write it next to the video in the requested format
+async fn save_track(client: &Client, track: &SubtitleTrack, video: &Path, format: SubtitleFormat) -> Result<PathBuf> {
+    let text = client.get(track.url.clone())
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    let converted = convert(&text, &track.ext, format)?;
+    let path = sidecar_path(video, &track.language, format);
+    tokio::fs::write(&path, converted).await?;
+    Ok(path)
+}
+
+fn sidecar_path(video: &Path, language: &str, format: SubtitleFormat) -> PathBuf {
+    let stem = video.file_stem().unwrap_or_default().to_string_lossy();
+    video.with_file_name(format!("{}.{}.{}", stem, language, format.extension()))
+}
+
+/// Mux subtitle files into the video as soft subtitle streams, replacing the original
+async fn embed(video: &Path, subtitles: &[(PathBuf, String)]) -> Result<()> {
+    let extension = video.extension().and_then(OsStr::to_str).unwrap_or("mp4").to_lowercase();
+    let codec = match extension.as_str() {
+        "mkv" => "srt",
+        "webm" => "webvtt",
+        _ => "mov_text",
+    };
+
+    let temp = video.with_extension(format!("subs.{}", extension));
+
+    let mut args: Vec<String> = vec!["-i".into(), video.to_string_lossy().into_owned()];
+    for (path, _) in subtitles {
+        args.extend(["-i".into(), path.to_string_lossy().into_owned()]);
+    }
+    for input in 0..=subtitles.len() {
+        args.extend(["-map".into(), input.to_string()]);
+    }
+    args.extend(["-c".into(), "copy".into(), "-c:s".into(), codec.into()]);
+    for (index, (_, language)) in subtitles.iter().enumerate() {
+        args.extend([format!("-metadata:s:s:{}", index), format!("language={}", language)]);
+    }
+    args.push(temp.to_string_lossy().into_owned());
+
+    ffmpeg::run(args).await?;
+    tokio::fs::rename(&temp, video).await?;
+    Ok(())
+}
+
+/// Convert subtitles between SubRip and WebVTT; other formats are rejected
+pub fn convert(text: &str, from_ext: &str, to: SubtitleFormat) -> Result<String> {
+    let from = from_ext.parse::<SubtitleFormat>()?;
+    if from == to {
+        return Ok(text.to_string());
+    }
+
+    let cues = parse_cues(text);
+    if cues.is_empty() && !text.trim().is_empty() {
+        return Err(Error::OutputParsing(format!("No subtitle cues found in {} file", from_ext)));
+    }
+
+    Ok(match to {
+        SubtitleFormat::Srt => write_srt(&cues),
+        SubtitleFormat::Vtt => write_vtt(&cues),
+    })
+}
+
+/// Parse the cues of an SRT or WebVTT file; headers, notes and styles are skipped
+fn parse_cues(text: &str) -> Vec<Cue> {
+    let text = text.replace("\r\n", "\n");
+
+    text.split("\n\n")
+        .filter_map(|block| {
+            let lines: Vec<&str> = block.lines().collect();
+            let timing = lines.iter().position(|line| line.contains("-->"))?;
+            let (start, end) = lines[timing].split_once("-->")?;
+            let end = end.split_whitespace().next()?;
+
+            let text = lines[timing + 1..].join("\n");
+            if text.trim().is_empty() {
+                return None;
+            }
+
+            Some(Cue {
+                start: parse_timestamp(start)?,
+                end: parse_timestamp(end)?,
+                text,
+            })
+        })
+        .collect()
+}
+
+/// Parse `HH:MM:SS.mmm`, `MM:SS.mmm` or SRT's `HH:MM:SS,mmm` into milliseconds
+fn parse_timestamp(value: &str) -> Option<u64> {
+    let value = value.trim().replace(',', ".");
+    let parts: Vec<&str> = value.split(':').collect();
+    let (hours, minutes, seconds) = match parts.as_slice() {
+        [h, m, s] => (h.parse::<u64>().ok()?, m.parse::<u64>().ok()?, s.parse::<f64>().ok()?),
+        [m, s] => (0, m.parse::<u64>().ok()?, s.parse::<f64>().ok()?),
+        _ => return None,
+    };
+    Some((hours * 3600 + minutes * 60) * 1000 + (seconds * 1000.0).round() as u64)
+}
+
+fn format_timestamp(ms: u64, separator: char) -> String {
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        separator,
+        ms % 1000
+    )
+}
+
+fn write_srt(cues: &[Cue]) -> String {
+    // SubRip only understands basic styling, so drop WebVTT voice, class and timestamp tags
+    let tags = Regex::new(r"</?[^>]*>").unwrap();
+
+    cues.iter()
+        .enumerate()
+        .map(|(index, cue)| {
+            let text = tags.replace_all(&cue.text, |caps: &regex::Captures| {
+                let tag = &caps[0];
+                if ["<i>", "</i>", "<b>", "</b>", "<u>", "</u>"].contains(&tag) { tag.to_string() } else { String::new() }
+            });
+            let text = text.replace("&lt;", "<").replace("&gt;", ">").replace("&nbsp;", " ").replace("&amp;", "&");
+
+            format!(
+                "{}\n{} --> {}\n{}\n",
+                index + 1,
+                format_timestamp(cue.start, ','),
+                format_timestamp(cue.end, ','),
+                text
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn write_vtt(cues: &[Cue]) -> String {
+    let mut output = String::from("WEBVTT\n");
+    for cue in cues {
+        output.push_str(&format!(
+            "\n{} --> {}\n{}\n",
+            format_timestamp(cue.start, '.'),
+            format_timestamp(cue.end, '.'),
+            cue.text
+        ));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use url::Url;
+
+    #[test]
+    fn test_convert_subtitles() {
+        let vtt = "WEBVTT\nKind: captions\n\nNOTE generated\n\nintro\n00:01.500 --> 00:04.000 align:start\n<v Speaker>Hello &amp; <i>welcome</i></v>\n\n01:02:03.004 --> 01:02:05.000\nSecond line\n";
+        let srt = convert(vtt, "vtt", SubtitleFormat::Srt).unwrap();
+        assert_eq!(srt, "1\n00:00:01,500 --> 00:00:04,000\nHello & <i>welcome</i>\n\n2\n01:02:03,004 --> 01:02:05,000\nSecond line\n");
+
+        let back = convert(&srt, "srt", SubtitleFormat::Vtt).unwrap();
+        assert!(back.starts_with("WEBVTT\n\n00:00:01.500 --> 00:00:04.000\n"));
+        assert!(convert("garbage", "srt", SubtitleFormat::Vtt).is_err());
+    }
+
+    #[test]
+    fn test_select_tracks() {
+        let track = |language: &str, auto_generated| SubtitleTrack {
+            language: language.to_string(),
+            name: None,
+            url: Url::parse("https://example.com/subs.vtt").unwrap(),
+            ext: "vtt".to_string(),
+            auto_generated,
+        };
+        let tracks = vec![track("en-US", false), track("en", true), track("fr", true), track("de", false)];
+
+        let mut options = SubtitleOptions {
+            languages: vec!["en".to_string(), "fr".to_string()],
+            include_auto: false,
+            format: SubtitleFormat::Srt,
+            write: true,
+            embed: false,
+        };
+        let languages = |options: &SubtitleOptions| {
+            select_tracks(&tracks, options).iter().map(|t| t.language.clone()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(languages(&options), ["en-US"]);
+        options.include_auto = true;
+        assert_eq!(languages(&options), ["en-US", "fr"]);
+        options.languages = vec!["all".to_string()];
+        assert_eq!(languages(&options), ["en-US", "de", "en", "fr"]);
+
+        // Formats that can't be converted are never picked
+        let json3 = SubtitleTrack { ext: "json3".to_string(), ..track("es", false) };
+        assert!(select_tracks(&[json3], &options).is_empty());
+    }
+}
@@ -0,0 +1,200 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use log::warn;
+use reqwest::Client;
+
+use crate::utils::ffmpeg;
+use crate::{Result, Thumbnail, VideoInfo};
+
+/// What to do with the best thumbnail of a video
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThumbnailOptions {
+    /// Keep the image as a sidecar file next to the video
+    pub write: bool,
+    /// Attach the image to the file as cover art
+    pub embed: bool,
+}
+
+/// Download the best thumbnail of `info` next to `file` and embed it when requested.
+///
+/// The thumbnail is an extra: a failed fetch or embed is reported as a warning without
+/// failing the download.
+pub async fn process(info: &VideoInfo, file: &Path, options: &ThumbnailOptions) -> Result<()> {
+    let thumbnail = match info.best_thumbnail() {
+        Some(thumbnail) => thumbnail,
+        None => {
+            warn!("No thumbnail available for {}", info.title);
+            return Ok(());
+        }
+    };
+
+    let image = match save(thumbnail, file).await {
+        Ok(image) => image,
+        Err(e) => {
+            warn!("Could not download the thumbnail of {}: {}", info.title, e);
+            return Ok(());
+        }
+    };
+
+    if options.embed {
+        if let Err(e) = embed(file, &image).await {
+            warn!("Could not embed the thumbnail: {}", e);
+        }
+    }
+
+    if !options.write {
+        let _ = tokio::fs::remove_file(&image).await;
+    }
+
+    Ok(())
+}
+
+/// Fetch a thumbnail and write it next to `file`, named after the image type
+async fn save(thumbnail: &Thumbnail, file: &Path) -> Result<PathBuf> {
+    let response = Client::new()
+        .get(thumbnail.url.clone())
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let content_type = response.headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let bytes = response.bytes().await?;
+
+    let image = file.with_extension(image_extension(thumbnail, content_type.as_deref()));
+    tokio::fs::write(&image, &bytes).await?;
+    Ok(image)
+}
+
+/// Image extension from the response's content type, falling back to the URL
+fn image_extension(thumbnail: &Thumbnail, content_type: Option<&str>) -> &'static str {
+    let from_url = || {
+        let path = thumbnail.url.path().to_lowercase();
+        Path::new(&path).extension().and_then(OsStr::to_str).map(str::to_string)
+    };
+
+    let kind = match content_type {
+        Some(content_type) => content_type.split(';').next().unwrap_or("").trim().to_lowercase(),
+        None => String::new(),
+    };
+
+    match kind.as_str() {
+        "image/webp" => "webp",
+        "image/png" => "png",
+        "image/jpeg" | "image/jpg" => "jpg",
+        _ => match from_url().as_deref() {
+            Some("webp") => "webp",
+            Some("png") => "png",
+            _ => "jpg",
+        },
+    }
+}
+
+/// Attach `image` to `file` as cover art, replacing the original
+async fn embed(file: &Path, image: &Path) -> Result<()> {
+    let extension = file.extension().and_then(OsStr::to_str).unwrap_or("mp4").to_lowercase();
+
+    if matches!(extension.as_str(), "opus" | "ogg") {
+        warn!("Embedding thumbnails is not supported for .{} files, skipping", extension);
+        return Ok(());
+    }
+
+    // MP4 and MP3 cover art must be JPEG or PNG
+    let converted = if image.extension().and_then(OsStr::to_str) == Some("webp") {
+        let jpeg = image.with_extension("cover.jpg");
+        ffmpeg::run(vec![
+            "-i".into(),
+            image.to_string_lossy().into_owned(),
+            jpeg.to_string_lossy().into_owned(),
+        ]).await?;
+        Some(jpeg)
+    } else {
+        None
+    };
+    let cover = converted.as_deref().unwrap_or(image);
+
+    let temp = file.with_extension(format!("cover.{}", extension));
+    let result = ffmpeg::run(embed_args(file, cover, &extension, &temp)).await;
+
+    if let Some(jpeg) = &converted {
+        let _ = tokio::fs::remove_file(jpeg).await;
+    }
+    if let Err(e) = result {
+        let _ = tokio::fs::remove_file(&temp).await;
+        return Err(e);
+    }
+
+    tokio::fs::rename(&temp, file).await?;
+    Ok(())
+}
+
+fn embed_args(file: &Path, cover: &Path, extension: &str, output: &Path) -> Vec<String> {
+    let file = file.to_string_lossy().into_owned();
+    let cover = cover.to_string_lossy().into_owned();
+
+    let mut args: Vec<String> = vec!["-i".into(), file];
+    match extension {
+        // Matroska stores cover art as an attachment rather than a stream
+        "mkv" | "mka" | "webm" => {
+            let (mimetype, name) = if cover.ends_with(".png") {
+                ("image/png", "cover.png")
+            } else {
+                ("image/jpeg", "cover.jpg")
+            };
+            args.extend([
+                "-map".into(), "0".into(),
+                "-c".into(), "copy".into(),
+                "-attach".into(), cover,
+                "-metadata:s:t".into(), format!("mimetype={}", mimetype),
+                "-metadata:s:t".into(), format!("filename={}", name),
+            ]);
+        }
+        "mp3" => {
+            args.extend([
+                "-i".into(), cover,
+                "-map".into(), "0:a".into(),
+                "-map".into(), "1".into(),
+                "-c".into(), "copy".into(),
+                "-id3v2_version".into(), "3".into(),
+                "-metadata:s:v".into(), "title=Album cover".into(),
+                "-metadata:s:v".into(), "comment=Cover (front)".into(),
+            ]);
+        }
+        // MP4 family and FLAC take the image as an extra video stream marked as attached
+        // picture; it follows the video stream unless the file is audio-only
+        _ => {
+            let cover_stream = if matches!(extension, "m4a" | "flac") { 0 } else { 1 };
+            args.extend([
+                "-i".into(), cover,
+                "-map".into(), "0".into(),
+                "-map".into(), "1".into(),
+                "-c".into(), "copy".into(),
+                format!("-disposition:v:{}", cover_stream), "attached_pic".into(),
+            ]);
+        }
+    }
+    args.push(output.to_string_lossy().into_owned());
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use url::Url;
+
+    #[test]
+    fn test_image_extension() {
+        let thumbnail = Thumbnail {
+            url: Url::parse("https://i.ytimg.com/vi_webp/abc/maxresdefault.webp").unwrap(),
+            width: None,
+            height: None,
+        };
+
+        assert_eq!(image_extension(&thumbnail, Some("image/png")), "png");
+        assert_eq!(image_extension(&thumbnail, Some("image/jpeg; charset=binary")), "jpg");
+        assert_eq!(image_extension(&thumbnail, None), "webp");
+        assert_eq!(image_extension(&thumbnail, Some("application/octet-stream")), "webp");
+    }
+}
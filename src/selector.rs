@@ -0,0 +1,552 @@
+use std::cmp::Ordering;
+
+use crate::{Error, Quality, Result, VideoFormat};
+
+/// Container extensions accepted as a shorthand for "the best format in this container"
+const EXTENSIONS: [&str; 10] = ["mp4", "webm", "m4a", "mov", "mkv", "flv", "3gp", "mp3", "ogg", "opus"];
+
+/// A format selection expression such as `bestvideo[height<=1080][vcodec^=avc]+bestaudio/best`.
+///
+/// The expression is a list of alternatives separated by `/`; the first alternative whose
+/// selections all match wins. An alternative merges one or more selections with `+`, and a
+/// selection is a base followed by any number of `[field op value]` filters.
+///
+/// Bases:
+/// - `best`/`b`, `worst`/`w`: formats with both video and audio
+/// - `bestvideo`/`bv`, `worstvideo`/`wv`: video-only formats (`bv*`/`wv*` also allow muxed ones)
+/// - `bestaudio`/`ba`, `worstaudio`/`wa`: audio-only formats (`ba*`/`wa*` also allow muxed ones)
+/// - a container such as `mp4`: the best format in that container
+/// - anything else is a format ID
+///
+/// Numeric fields (`height`, `width`, `fps`, `filesize`, `tbr`, `vbr`, `abr`) support `=`, `!=`,
+/// `<`, `<=`, `>` and `>=`, and values may use `K`, `M` and `G` (or `KiB`, `MiB`, `GiB`) suffixes.
+/// String fields (`id`, `ext`, `vcodec`, `acodec`, `language`) support `=`, `!=`, `^=` (starts
+/// with), `$=` (ends with) and `*=` (contains). A `?` after the operator also accepts formats
+/// where the field is unknown, e.g. `[height<=?720]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatSelector {
+    alternatives: Vec<Vec<Selection>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Selection {
+    base: Base,
+    filters: Vec<Filter>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Base {
+    Ranked(Rank, Streams),
+    Id(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Rank {
+    Best,
+    Worst,
+}
+
+/// Which streams a ranked base considers
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Streams {
+    Muxed,
+    VideoOnly,
+    AnyVideo,
+    AudioOnly,
+    AnyAudio,
+    Any,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Filter {
+    field: Field,
+    op: Op,
+    value: Value,
+    /// Accept formats where the field is unknown
+    optional: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Height,
+    Width,
+    Fps,
+    Filesize,
+    Tbr,
+    Vbr,
+    Abr,
+    Id,
+    Ext,
+    Vcodec,
+    Acodec,
+    Language,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    StartsWith,
+    EndsWith,
+    Contains,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Number(f64),
+    Text(String),
+}
+
+impl FormatSelector {
+    /// Parse a selector expression
+    pub fn parse(expression: &str) -> Result<Self> {
+        let invalid = |reason: &str| Error::InvalidArgument(format!("Invalid format selector '{}': {}", expression, reason));
+
+        let alternatives = split_top_level(expression, '/')
+            .into_iter()
+            .map(|alternative| {
+                split_top_level(alternative, '+')
+                    .into_iter()
+                    .map(|selection| Selection::parse(selection.trim()).map_err(|reason| invalid(&reason)))
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { alternatives })
+    }
+
+    /// The selector used when no expression is given: the best (or worst) video up to the
+    /// height of `quality` in the `format` container merged with the best audio, falling back
+    /// to muxed formats, other containers and finally whatever is available.
+    pub fn from_preferences(quality: &str, format: &str) -> Result<Self> {
+        let (video, audio, muxed) = if quality.eq_ignore_ascii_case("worst") {
+            ("wv", "wa", "w")
+        } else {
+            ("bv", "ba", "b")
+        };
+
+        let limit = quality_height(quality)
+            .map(|height| format!("[height<=?{}]", height))
+            .unwrap_or_default();
+        let ext = format!("[ext={}]", format.trim().to_lowercase());
+
+        Self::parse(&format!(
+            "{v}{l}{e}+{a}/{m}{l}{e}/{v}{l}+{a}/{m}{l}/{v}*{l}/{m}/{v}+{a}/{v}*/{a}*",
+            v = video, a = audio, m = muxed, l = limit, e = ext
+        ))
+    }
+
+    /// Pick the formats of the first matching alternative
+    pub fn select<'a>(&self, formats: &'a [VideoFormat]) -> Option<Vec<&'a VideoFormat>> {
+        self.alternatives.iter().find_map(|alternative| {
+            alternative.iter()
+                .map(|selection| selection.select(formats))
+                .collect::<Option<Vec<_>>>()
+        })
+    }
+
+    /// The IDs of the selected formats joined with `+`, as accepted by `Platform::download_video`
+    pub fn select_id(&self, formats: &[VideoFormat]) -> Result<String> {
+        let selected = self.select(formats).ok_or(Error::NoSuitableFormats)?;
+        Ok(selected.iter().map(|f| f.id.as_str()).collect::<Vec<_>>().join("+"))
+    }
+}
+
+/// Height limit of a quality name such as `720p`, `hd1080` or `medium`; `None` for no limit
+pub(crate) fn quality_height(quality: &str) -> Option<u32> {
+    match quality.trim().to_lowercase().as_str() {
+        "best" | "worst" | "" => None,
+        "low" => Quality::Low.max_height(),
+        "medium" => Quality::Medium.max_height(),
+        "high" | "hd720" | "hd" => Quality::HD720.max_height(),
+        "hd1080" | "fullhd" => Quality::HD1080.max_height(),
+        "uhd2160" | "4k" => Quality::UHD2160.max_height(),
+        other => Quality::Custom(other.to_string()).max_height(),
+    }
+}
+
+/// Split on `separator`, ignoring separators inside `[...]` filters
+fn split_top_level(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+
+    for (i, c) in text.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth = depth.saturating_sub(1),
+            c if c == separator && depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+impl Selection {
+    fn parse(text: &str) -> std::result::Result<Self, String> {
+        let (name, mut rest) = text.split_at(text.find('[').unwrap_or(text.len()));
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("empty selection".to_string());
+        }
+
+        let mut filters = Vec::new();
+        while !rest.trim().is_empty() {
+            let inner = rest.trim_start()
+                .strip_prefix('[')
+                .ok_or_else(|| format!("unexpected '{}'", rest.trim()))?;
+            let end = inner.find(']').ok_or("unclosed '['")?;
+            filters.push(Filter::parse(&inner[..end])?);
+            rest = &inner[end + 1..];
+        }
+
+        let ranked = |rank, streams| Base::Ranked(rank, streams);
+        let base = match name {
+            "best" | "b" => ranked(Rank::Best, Streams::Muxed),
+            "worst" | "w" => ranked(Rank::Worst, Streams::Muxed),
+            "bestvideo" | "bv" => ranked(Rank::Best, Streams::VideoOnly),
+            "worstvideo" | "wv" => ranked(Rank::Worst, Streams::VideoOnly),
+            "bestvideo*" | "bv*" => ranked(Rank::Best, Streams::AnyVideo),
+            "worstvideo*" | "wv*" => ranked(Rank::Worst, Streams::AnyVideo),
+            "bestaudio" | "ba" => ranked(Rank::Best, Streams::AudioOnly),
+            "worstaudio" | "wa" => ranked(Rank::Worst, Streams::AudioOnly),
+            "bestaudio*" | "ba*" => ranked(Rank::Best, Streams::AnyAudio),
+            "worstaudio*" | "wa*" => ranked(Rank::Worst, Streams::AnyAudio),
+            "best*" | "b*" => ranked(Rank::Best, Streams::Any),
+            "worst*" | "w*" => ranked(Rank::Worst, Streams::Any),
+            ext if EXTENSIONS.contains(&ext) => {
+                filters.push(Filter {
+                    field: Field::Ext,
+                    op: Op::Eq,
+                    value: Value::Text(ext.to_string()),
+                    optional: false,
+                });
+                ranked(Rank::Best, Streams::Any)
+            }
+            id => Base::Id(id.to_string()),
+        };
+
+        Ok(Self { base, filters })
+    }
+
+    fn select<'a>(&self, formats: &'a [VideoFormat]) -> Option<&'a VideoFormat> {
+        let mut candidates = formats.iter()
+            .filter(|format| self.filters.iter().all(|filter| filter.matches(format)));
+
+        match &self.base {
+            Base::Id(id) => candidates.find(|format| &format.id == id),
+            Base::Ranked(rank, streams) => {
+                let wanted = match rank {
+                    Rank::Best => Ordering::Greater,
+                    Rank::Worst => Ordering::Less,
+                };
+
+                // Ties keep the earlier format, since platforms list their preferred formats first
+                candidates
+                    .filter(|format| streams.matches(format))
+                    .fold(None, |chosen: Option<&VideoFormat>, format| match chosen {
+                        Some(current) if streams.compare(format, current) != wanted => Some(current),
+                        _ => Some(format),
+                    })
+            }
+        }
+    }
+}
+
+impl Streams {
+    fn matches(self, format: &VideoFormat) -> bool {
+        match self {
+            Streams::Muxed => format.has_video && format.has_audio,
+            Streams::VideoOnly => format.has_video && !format.has_audio,
+            Streams::AnyVideo => format.has_video,
+            Streams::AudioOnly => format.has_audio && !format.has_video,
+            Streams::AnyAudio => format.has_audio,
+            Streams::Any => format.has_video || format.has_audio,
+        }
+    }
+
+    /// Compare formats by the quality of the streams being selected
+    fn compare(self, a: &VideoFormat, b: &VideoFormat) -> Ordering {
+        let key = |f: &VideoFormat| -> [f64; 4] {
+            let number = |field| Field::number(field, f).unwrap_or(0.0);
+            match self {
+                Streams::AudioOnly | Streams::AnyAudio => {
+                    [number(Field::Abr), number(Field::Tbr), number(Field::Filesize), 0.0]
+                }
+                _ => [number(Field::Height), number(Field::Fps), number(Field::Tbr), number(Field::Filesize)],
+            }
+        };
+
+        key(a).iter()
+            .zip(key(b).iter())
+            .map(|(x, y)| x.total_cmp(y))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl Filter {
+    fn parse(text: &str) -> std::result::Result<Self, String> {
+        const OPERATORS: [(&str, Op); 9] = [
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("!=", Op::Ne),
+            ("^=", Op::StartsWith),
+            ("$=", Op::EndsWith),
+            ("*=", Op::Contains),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+            ("=", Op::Eq),
+        ];
+
+        let key_end = text.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(text.len());
+        let (key, rest) = text.split_at(key_end);
+        let rest = rest.trim_start();
+
+        let field = Field::from_name(key.trim()).ok_or_else(|| format!("unknown field '{}'", key.trim()))?;
+
+        let (symbol, op) = OPERATORS.iter()
+            .find(|(symbol, _)| rest.starts_with(symbol))
+            .ok_or_else(|| format!("missing operator in '[{}]'", text))?;
+        let rest = &rest[symbol.len()..];
+
+        let (optional, raw) = match rest.strip_prefix('?') {
+            Some(raw) => (true, raw),
+            None => (false, rest),
+        };
+        let raw = raw.trim().trim_matches(|c| c == '"' || c == '\'');
+        if raw.is_empty() {
+            return Err(format!("missing value in '[{}]'", text));
+        }
+
+        let value = if field.is_numeric() {
+            if matches!(op, Op::StartsWith | Op::EndsWith | Op::Contains) {
+                return Err(format!("'{}' only applies to text fields", symbol));
+            }
+            Value::Number(parse_number(raw).ok_or_else(|| format!("'{}' is not a number", raw))?)
+        } else {
+            if matches!(op, Op::Lt | Op::Le | Op::Gt | Op::Ge) {
+                return Err(format!("'{}' only applies to numeric fields", symbol));
+            }
+            Value::Text(raw.to_string())
+        };
+
+        Ok(Self { field, op: *op, value, optional })
+    }
+
+    fn matches(&self, format: &VideoFormat) -> bool {
+        match &self.value {
+            Value::Number(expected) => match self.field.number(format) {
+                Some(actual) => match self.op {
+                    Op::Eq => actual == *expected,
+                    Op::Ne => actual != *expected,
+                    Op::Lt => actual < *expected,
+                    Op::Le => actual <= *expected,
+                    Op::Gt => actual > *expected,
+                    Op::Ge => actual >= *expected,
+                    Op::StartsWith | Op::EndsWith | Op::Contains => false,
+                },
+                None => self.optional,
+            },
+            Value::Text(expected) => match self.field.text(format) {
+                Some(actual) => match self.op {
+                    Op::Eq => actual == *expected,
+                    Op::Ne => actual != *expected,
+                    Op::StartsWith => actual.starts_with(expected.as_str()),
+                    Op::EndsWith => actual.ends_with(expected.as_str()),
+                    Op::Contains => actual.contains(expected.as_str()),
+                    Op::Lt | Op::Le | Op::Gt | Op::Ge => false,
+                },
+                None => self.optional,
+            },
+        }
+    }
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "height" => Field::Height,
+            "width" => Field::Width,
+            "fps" => Field::Fps,
+            "filesize" => Field::Filesize,
+            "tbr" => Field::Tbr,
+            "vbr" => Field::Vbr,
+            "abr" => Field::Abr,
+            "id" | "format_id" => Field::Id,
+            "ext" => Field::Ext,
+            "vcodec" => Field::Vcodec,
+            "acodec" => Field::Acodec,
+            "language" => Field::Language,
+            _ => return None,
+        })
+    }
+
+    fn is_numeric(self) -> bool {
+        matches!(
+            self,
+            Field::Height | Field::Width | Field::Fps | Field::Filesize | Field::Tbr | Field::Vbr | Field::Abr
+        )
+    }
+
+    fn number(self, format: &VideoFormat) -> Option<f64> {
+        match self {
+            Field::Height => format.height.map(f64::from),
+            Field::Width => format.width.map(f64::from),
+            Field::Fps => format.fps,
+            Field::Filesize => format.file_size.map(|size| size as f64),
+            Field::Vbr => format.video_bitrate.filter(|_| format.has_video).map(f64::from),
+            Field::Abr => format.audio_bitrate.map(f64::from),
+            Field::Tbr => match (format.video_bitrate, format.audio_bitrate) {
+                (Some(video), Some(audio)) => Some(f64::from(video + audio)),
+                (video, audio) => video.or(audio).map(f64::from),
+            },
+            _ => None,
+        }
+    }
+
+    fn text(self, format: &VideoFormat) -> Option<String> {
+        match self {
+            Field::Id => Some(format.id.clone()),
+            Field::Ext => Some(format.format.to_string()),
+            // Missing streams read as "none", so `[acodec=none]` matches video-only formats
+            Field::Vcodec if !format.has_video => Some("none".to_string()),
+            Field::Vcodec => format.vcodec.clone(),
+            Field::Acodec if !format.has_audio => Some("none".to_string()),
+            Field::Acodec => format.acodec.clone(),
+            Field::Language => format.language.clone(),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a number with an optional size suffix, e.g. `720`, `2.5M` or `50MiB`
+fn parse_number(text: &str) -> Option<f64> {
+    const SUFFIXES: [(&str, f64); 9] = [
+        ("kib", 1024.0),
+        ("mib", 1024.0 * 1024.0),
+        ("gib", 1024.0 * 1024.0 * 1024.0),
+        ("kb", 1e3),
+        ("mb", 1e6),
+        ("gb", 1e9),
+        ("k", 1e3),
+        ("m", 1e6),
+        ("g", 1e9),
+    ];
+
+    let lower = text.trim().to_lowercase();
+    let (digits, multiplier) = SUFFIXES.iter()
+        .find_map(|(suffix, multiplier)| lower.strip_suffix(suffix).map(|digits| (digits, *multiplier)))
+        .unwrap_or((lower.as_str(), 1.0));
+
+    digits.trim().parse::<f64>().ok().map(|n| n * multiplier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Format;
+
+    fn video(id: &str, height: u32, vcodec: &str, has_audio: bool, size: u64) -> VideoFormat {
+        VideoFormat {
+            height: Some(height),
+            vcodec: Some(vcodec.to_string()),
+            acodec: has_audio.then(|| "mp4a.40.2".to_string()),
+            file_size: Some(size),
+            has_audio,
+            ..VideoFormat::new(id, Quality::from_height(height), Format::MP4)
+        }
+    }
+
+    fn audio(id: &str, kbps: u32) -> VideoFormat {
+        VideoFormat {
+            acodec: Some("opus".to_string()),
+            audio_bitrate: Some(kbps),
+            has_video: false,
+            ..VideoFormat::new(id, Quality::Custom(format!("audio {}k", kbps)), Format::WebM)
+        }
+    }
+
+    fn formats() -> Vec<VideoFormat> {
+        vec![
+            video("18", 360, "avc1.42001E", true, 10_000_000),
+            video("137", 1080, "avc1.640028", false, 90_000_000),
+            video("248", 1080, "vp9", false, 70_000_000),
+            video("136", 720, "avc1.4d401f", false, 40_000_000),
+            video("401", 2160, "av01.0.12M.08", false, 300_000_000),
+            audio("249", 50),
+            audio("251", 160),
+        ]
+    }
+
+    #[test]
+    fn test_parse_grammar() {
+        let selector = FormatSelector::parse("bestvideo[height<=1080][vcodec^=avc]+bestaudio/best").unwrap();
+        assert_eq!(selector.alternatives.len(), 2);
+        assert_eq!(selector.alternatives[0].len(), 2);
+        assert_eq!(selector.alternatives[0][0].base, Base::Ranked(Rank::Best, Streams::VideoOnly));
+        assert_eq!(selector.alternatives[0][0].filters, vec![
+            Filter { field: Field::Height, op: Op::Le, value: Value::Number(1080.0), optional: false },
+            Filter { field: Field::Vcodec, op: Op::StartsWith, value: Value::Text("avc".into()), optional: false },
+        ]);
+        assert_eq!(selector.alternatives[1][0].base, Base::Ranked(Rank::Best, Streams::Muxed));
+
+        let selector = FormatSelector::parse("best[filesize<50M]").unwrap();
+        assert_eq!(selector.alternatives[0][0].filters[0].value, Value::Number(50e6));
+
+        let selector = FormatSelector::parse("dash-720+dash-audio").unwrap();
+        assert_eq!(selector.alternatives[0][1].base, Base::Id("dash-audio".into()));
+
+        assert_eq!(parse_number("1.5KiB"), Some(1536.0));
+        assert!(FormatSelector::parse("bv[height<=?720]").unwrap().alternatives[0][0].filters[0].optional);
+
+        for invalid in ["", "best/", "bv[height<=abc]", "bv[resolution=1]", "bv[height", "bv[vcodec>avc]", "bv[height^=1]"] {
+            assert!(FormatSelector::parse(invalid).is_err(), "{} should not parse", invalid);
+        }
+    }
+
+    #[test]
+    fn test_select_formats() {
+        let formats = formats();
+        let select = |expression: &str| FormatSelector::parse(expression).unwrap().select_id(&formats).ok();
+
+        assert_eq!(select("bestvideo[height<=1080][vcodec^=avc]+bestaudio/best").as_deref(), Some("137+251"));
+        assert_eq!(select("bv+ba").as_deref(), Some("401+251"));
+        assert_eq!(select("best").as_deref(), Some("18"));
+        assert_eq!(select("worst").as_deref(), Some("18"));
+        assert_eq!(select("wa").as_deref(), Some("249"));
+        assert_eq!(select("bv[height=720]").as_deref(), Some("136"));
+        assert_eq!(select("bv*[filesize<50M]").as_deref(), Some("136"));
+        assert_eq!(select("bv[vcodec*=vp9]").as_deref(), Some("248"));
+        assert_eq!(select("bv[acodec!=none]/ba").as_deref(), Some("251"));
+        assert_eq!(select("webm").as_deref(), Some("251"));
+        assert_eq!(select("999/136").as_deref(), Some("136"));
+        assert_eq!(select("bv[height>4000]"), None);
+    }
+
+    #[test]
+    fn test_selector_from_preferences() {
+        let formats = formats();
+        let select = |quality: &str, format: &str| {
+            FormatSelector::from_preferences(quality, format).unwrap().select_id(&formats).unwrap()
+        };
+
+        assert_eq!(select("best", "mp4"), "401+251");
+        assert_eq!(select("1080p", "mp4"), "137+251");
+        assert_eq!(select("hd720", "mp4"), "136+251");
+        assert_eq!(select("worst", "mp4"), "136+249");
+
+        // Muxed-only sources such as TikTok fall back to `best`
+        let muxed = vec![video("default", 1024, "h264", true, 0)];
+        let selector = FormatSelector::from_preferences("720p", "mp4").unwrap();
+        assert_eq!(selector.select_id(&muxed).unwrap(), "default");
+    }
+}
@@ -0,0 +1,200 @@
+use std::path::PathBuf;
+use regex::Regex;
+
+use crate::{Error, Result, VideoInfo};
+
+/// Fields that can appear in a template as `{name}`
+const FIELDS: &[&str] = &["title", "id", "uploader", "upload_date", "platform", "height", "ext", "index"];
+
+/// Longest file or directory name produced, in bytes; most filesystems allow 255
+const MAX_NAME_BYTES: usize = 200;
+
+/// Placeholder for fields the video does not have
+const MISSING: &str = "NA";
+
+/// Output file names built from video information, such as `{uploader}/{title} [{id}].{ext}`.
+///
+/// `/` separates directories. Every field value is made safe for file names, and each
+/// directory and file name is shortened to fit filesystem limits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputTemplate {
+    template: String,
+}
+
+/// Values for a template that come from the download rather than the video
+#[derive(Debug, Clone, Default)]
+pub struct TemplateContext<'a> {
+    /// Selected format ID, with `+` joining separate video and audio streams
+    pub format_id: &'a str,
+    /// 1-based position in a playlist or batch
+    pub index: Option<usize>,
+}
+
+impl OutputTemplate {
+    pub fn parse(template: &str) -> Result<Self> {
+        let invalid = |reason: String| Error::InvalidArgument(format!("Invalid output template \"{}\": {}", template, reason));
+
+        if template.trim().is_empty() {
+            return Err(invalid("template is empty".into()));
+        }
+        if template.starts_with('/') || template.starts_with('\\') {
+            return Err(invalid("template must be a relative path".into()));
+        }
+
+        for component in template.split(['/', '\\']) {
+            if component.is_empty() || component == "." || component == ".." {
+                return Err(invalid(format!("\"{}\" is not a valid file or directory name", component)));
+            }
+        }
+
+        for caps in field_regex().captures_iter(template) {
+            if !FIELDS.contains(&&caps[1]) {
+                return Err(invalid(format!("unknown field {{{}}}, expected one of {}", &caps[1], FIELDS.join(", "))));
+            }
+        }
+
+        Ok(Self { template: template.to_string() })
+    }
+
+    /// Relative path for a download of `info`
+    pub fn render(&self, info: &VideoInfo, context: &TemplateContext) -> PathBuf {
+        let re = field_regex();
+        let components: Vec<&str> = self.template.split(['/', '\\']).collect();
+        let last = components.len() - 1;
+
+        components.iter()
+            .enumerate()
+            .map(|(position, component)| {
+                let name = re.replace_all(component, |caps: &regex::Captures| {
+                    let value = field_value(info, context, &caps[1]);
+                    sanitize(&value)
+                });
+                let name = match sanitize(name.trim()) {
+                    name if name.is_empty() => MISSING.to_string(),
+                    name => name,
+                };
+                shorten(&name, position == last)
+            })
+            .collect()
+    }
+}
+
+impl Default for OutputTemplate {
+    fn default() -> Self {
+        Self { template: "{title}.{ext}".to_string() }
+    }
+}
+
+/// Make a name safe for file systems, leaving the length to [`shorten`]
+fn sanitize(name: &str) -> String {
+    let options = sanitize_filename::Options { truncate: false, ..Default::default() };
+    sanitize_filename::sanitize_with_options(name, options)
+}
+
+fn field_regex() -> Regex {
+    Regex::new(r"\{([a-z_]+)\}").unwrap()
+}
+
+fn field_value(info: &VideoInfo, context: &TemplateContext, field: &str) -> String {
+    let value = match field {
+        "title" => Some(info.title.clone()),
+        "id" => info.id.clone(),
+        "uploader" => info.uploader.clone().or_else(|| info.uploader_id.clone()),
+        "upload_date" => info.upload_date.clone(),
+        "platform" => Some(info.platform.clone()).filter(|p| !p.is_empty()),
+        "height" => selected_format(info, context.format_id)
+            .and_then(|format| format.height)
+            .map(|height| height.to_string()),
+        "ext" => Some(extension(info, context.format_id)),
+        "index" => context.index.map(|index| index.to_string()),
+        _ => None,
+    };
+    value.filter(|v| !v.trim().is_empty()).unwrap_or_else(|| MISSING.to_string())
+}
+
+fn selected_format<'a>(info: &'a VideoInfo, format_id: &str) -> Option<&'a crate::VideoFormat> {
+    let primary = format_id.split('+').next().unwrap_or(format_id);
+    info.formats.iter().find(|format| format.id == primary)
+}
+
+/// File extension of a download: the container of the selected format, or mp4 when
+/// separate video and audio streams are merged
+pub fn extension(info: &VideoInfo, format_id: &str) -> String {
+    match selected_format(info, format_id) {
+        Some(format) if !format_id.contains('+') && (format.has_audio || !format.has_video) => {
+            format.format.to_string()
+        }
+        _ => "mp4".to_string(),
+    }
+}
+
+/// Cut a name down to `MAX_NAME_BYTES`, keeping the extension of file names intact
+fn shorten(name: &str, is_file: bool) -> String {
+    if name.len() <= MAX_NAME_BYTES {
+        return name.to_string();
+    }
+
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if is_file && extension.len() < 16 => (stem, format!(".{}", extension)),
+        _ => (name, String::new()),
+    };
+
+    let mut end = MAX_NAME_BYTES.saturating_sub(extension.len());
+    while !stem.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}", stem[..end].trim_end(), extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use url::Url;
+    use crate::{Format, Quality, VideoFormat};
+
+    #[test]
+    fn test_render_template() {
+        let info = VideoInfo {
+            url: Url::parse("https://www.youtube.com/watch?v=dQw4w9WgXcQ").unwrap(),
+            id: Some("dQw4w9WgXcQ".to_string()),
+            platform: "YouTube".to_string(),
+            webpage_url: None,
+            title: "AC/DC: Live? ".to_string() + &"é".repeat(150),
+            description: None,
+            duration: None,
+            formats: vec![
+                VideoFormat { height: Some(1080), has_audio: false, ..VideoFormat::new("248", Quality::HD1080, Format::WebM) },
+                VideoFormat { has_video: false, ..VideoFormat::new("251", Quality::Low, Format::WebM) },
+            ],
+            subtitles: Vec::new(),
+            thumbnails: Vec::new(),
+            uploader: None,
+            uploader_id: None,
+            upload_date: Some("20091025".to_string()),
+            view_count: None,
+            like_count: None,
+            comment_count: None,
+            tags: Vec::new(),
+            categories: Vec::new(),
+            chapters: Vec::new(),
+            section: None,
+        };
+        let context = TemplateContext { format_id: "248+251", index: Some(3) };
+
+        let template = OutputTemplate::parse("{platform}/{uploader}/{index} - {upload_date} [{id}] {height}p.{ext}").unwrap();
+        assert_eq!(
+            template.render(&info, &context),
+            Path::new("YouTube/NA/3 - 20091025 [dQw4w9WgXcQ] 1080p.mp4")
+        );
+
+        let name = OutputTemplate::default().render(&info, &TemplateContext { format_id: "251", index: None });
+        let name = name.to_str().unwrap();
+        assert!(name.starts_with("ACDC Live ") && name.ends_with("é.webm"));
+        assert!(name.len() <= MAX_NAME_BYTES);
+
+        assert!(OutputTemplate::parse("{title}.{extension}").is_err());
+        assert!(OutputTemplate::parse("../{title}.{ext}").is_err());
+        assert!(OutputTemplate::parse("/tmp/{title}").is_err());
+    }
+}
@@ -1,6 +1,5 @@
-use std::path::Path;
 use tokio::process::Command;
-use crate::Config;
+use crate::{Config, Error, Result};
 
 /// Dependency check result
 pub struct DependencyStatus {
@@ -85,6 +84,20 @@ pub fn print_dependency_status(status: &DependencyStatus) {
     });
 }
 
+/// Fail when ffmpeg is missing, printing the dependency status; `purpose` names what it
+/// is needed for, such as "extracting audio"
+pub fn require_ffmpeg(status: &DependencyStatus, purpose: &str) -> Result<()> {
+    if status.ffmpeg_available {
+        return Ok(());
+    }
+    
+    print_dependency_status(status);
+    Err(Error::CommandExecution {
+        command: "ffmpeg".to_string(),
+        reason: format!("ffmpeg is required for {}", purpose)
+    })
+}
+
 /// Check if all required dependencies are available
 pub fn all_dependencies_available(status: &DependencyStatus) -> bool {
     status.yt_dlp_available && status.ffmpeg_available
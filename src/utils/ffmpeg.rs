@@ -0,0 +1,127 @@
+use std::ffi::OsStr;
+use std::path::Path;
+use tokio::process::Command;
+
+use crate::{Error, Result, Section};
+
+/// Check whether ffmpeg can be executed
+pub async fn is_available() -> bool {
+    Command::new("ffmpeg")
+        .arg("-version")
+        .output()
+        .await
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+/// Run ffmpeg with the given arguments, failing with its stderr if it exits unsuccessfully.
+///
+/// `-y`, `-hide_banner` and `-loglevel error` are always passed so ffmpeg never waits for
+/// input and only reports problems.
+pub async fn run<I, S>(args: I) -> Result<()>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let output = Command::new("ffmpeg")
+        .args(["-y", "-hide_banner", "-loglevel", "error"])
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| Error::CommandExecution {
+            command: "ffmpeg".to_string(),
+            reason: e.to_string()
+        })?;
+
+    if !output.status.success() {
+        return Err(Error::CommandExecution {
+            command: "ffmpeg".to_string(),
+            reason: String::from_utf8_lossy(&output.stderr).to_string()
+        });
+    }
+
+    Ok(())
+}
+
+/// Timestamps in seconds of the keyframes in the first video stream of `path`, found with ffprobe
+pub async fn keyframes(path: &Path) -> Result<Vec<f64>> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0", "-skip_frame", "nokey"])
+        .args(["-show_entries", "frame=pts_time", "-of", "csv=p=0"])
+        .arg(path)
+        .output()
+        .await
+        .map_err(|e| Error::CommandExecution {
+            command: "ffprobe".to_string(),
+            reason: e.to_string()
+        })?;
+
+    if !output.status.success() {
+        return Err(Error::CommandExecution {
+            command: "ffprobe".to_string(),
+            reason: String::from_utf8_lossy(&output.stderr).to_string()
+        });
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.trim().trim_end_matches(',').parse().ok())
+        .collect())
+}
+
+/// Codec name of the first audio stream of `path`, such as `aac` or `opus`, found with
+/// ffprobe; `None` if the file has no audio
+pub async fn audio_codec(path: &Path) -> Result<Option<String>> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "a:0"])
+        .args(["-show_entries", "stream=codec_name", "-of", "csv=p=0"])
+        .arg(path)
+        .output()
+        .await
+        .map_err(|e| Error::CommandExecution {
+            command: "ffprobe".to_string(),
+            reason: e.to_string()
+        })?;
+
+    if !output.status.success() {
+        return Err(Error::CommandExecution {
+            command: "ffprobe".to_string(),
+            reason: String::from_utf8_lossy(&output.stderr).to_string()
+        });
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.trim().trim_end_matches(',').to_string())
+        .find(|codec| !codec.is_empty()))
+}
+
+/// Video encoder settings for re-encoding `path` in a codec its container accepts
+pub fn video_encoder_args(path: &Path) -> &'static [&'static str] {
+    match path.extension().and_then(OsStr::to_str) {
+        Some("webm") => &["-c:v", "libvpx-vp9", "-b:v", "0", "-crf", "31", "-row-mt", "1"],
+        _ => &["-c:v", "libx264", "-preset", "veryfast", "-crf", "18"],
+    }
+}
+
+/// Copy `section` of `input` to `output`.
+///
+/// Fast cuts copy the streams and start at the keyframe before `section.start`; accurate
+/// cuts re-encode the video to start exactly on it.
+pub async fn cut(input: &Path, output: &Path, section: &Section) -> Result<()> {
+    let mut args: Vec<String> = vec![
+        "-ss".into(), section.start.to_string(),
+        "-i".into(), input.to_string_lossy().into_owned(),
+    ];
+    if let Some(duration) = section.duration() {
+        args.extend(["-t".into(), duration.to_string()]);
+    }
+    args.extend(["-map".into(), "0".into(), "-dn".into(), "-c".into(), "copy".into()]);
+    if section.accurate {
+        args.extend(video_encoder_args(output).iter().map(|arg| arg.to_string()));
+    }
+    args.extend(["-avoid_negative_ts".into(), "make_zero".into()]);
+    args.push(output.to_string_lossy().into_owned());
+
+    run(args).await
+}
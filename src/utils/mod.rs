@@ -1,2 +1,3 @@
 pub mod progress;
-pub mod dependency_check;
\ No newline at end of file
+pub mod dependency_check;
+pub mod ffmpeg;
\ No newline at end of file